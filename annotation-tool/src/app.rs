@@ -1,11 +1,18 @@
+use std::sync::Arc;
+
 use crate::storage::Storage;
 use egui::{self, InnerResponse, UiBuilder};
-use imanot::{AsyncRefTask, AsyncTask, ImageViewerInteraction, State, Tools};
+use imanot::{
+    AsyncRefTask, AsyncTask, AttributePanel, AttributeSchema, ClassId, DisplayMode, FilterChain,
+    HistogramPanel, ImageId, ImageViewerInteraction, LabelSchema, LayerPanel, MaskGeometry,
+    MaskSettings, State, Tools,
+};
 
 use image_selector::ImageSelector;
 
 mod config;
 mod image_selector;
+mod inspector;
 mod mask_generator;
 mod menu;
 #[cfg(not(target_arch = "wasm32"))]
@@ -27,6 +34,20 @@ pub(crate) struct ImageViewerApp {
     state: State,
     save_job: AsyncRefTask<Result<(), String>>,
     mask_generator: MaskGenerator,
+    histogram: HistogramPanel,
+    layers: LayerPanel,
+    display_mode: DisplayMode,
+    filters: FilterChain,
+    mask_settings: MaskSettings,
+    labels: Arc<LabelSchema>,
+    /// Class of newly created masks
+    active_class: Option<ClassId>,
+    mask_edit: inspector::MaskEditSettings,
+    gaps: inspector::GapFinder,
+    attributes: AttributeSchema,
+    attribute_panel: AttributePanel,
+    /// Geometry of the selected mask by image, mask revision and layer
+    selected_geometry: Option<((ImageId, u64, usize), MaskGeometry)>,
 }
 impl ImageViewerApp {
     pub fn new(storage: Box<dyn Storage>, tools: Tools, mask_generator: MaskGenerator) -> Self {
@@ -39,12 +60,48 @@ impl ImageViewerApp {
             state,
             save_job: AsyncRefTask::new_ready(Ok(())),
             mask_generator,
+            histogram: HistogramPanel::default(),
+            layers: LayerPanel::default(),
+            display_mode: DisplayMode::default(),
+            filters: FilterChain::default(),
+            mask_settings: MaskSettings::default(),
+            labels: Default::default(),
+            active_class: None,
+            mask_edit: Default::default(),
+            gaps: Default::default(),
+            attributes: Default::default(),
+            attribute_panel: Default::default(),
+            selected_geometry: None,
         }
     }
+
+    pub fn with_filters(mut self, filters: FilterChain) -> Self {
+        self.filters = filters;
+        self
+    }
+
+    pub fn with_mask_settings(mut self, settings: MaskSettings) -> Self {
+        self.mask_settings = settings;
+        self
+    }
+
+    pub fn with_labels(mut self, labels: LabelSchema) -> Self {
+        self.labels = Arc::new(labels);
+        self
+    }
+
+    pub fn with_attributes(mut self, attributes: AttributeSchema) -> Self {
+        self.attributes = attributes;
+        self
+    }
 }
 
 impl eframe::App for ImageViewerApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if let Some(class) = self.labels.hotkey_pressed(ctx) {
+            self.active_class = Some(class);
+        }
+        egui::SidePanel::right("inspector").show(ctx, |ui| self.inspector_ui(ui));
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.heading("Image pixel selector");
             self.menu_ui(ui);
@@ -0,0 +1,469 @@
+use egui::ComboBox;
+use imanot::{
+    AreaKind, ColorLut, ColorPalette, Connectivity, DisplayMode, FilterChain, ImageFilter,
+    ImageState, ImageStateLoaded, ImageViewer, LabelSchema, MaskImage, MorphologyOp, OverlayMode,
+    StructuringElement,
+};
+
+impl crate::app::ImageViewerApp {
+    pub(super) fn inspector_ui(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Display")
+            .default_open(true)
+            .show(ui, |ui| {
+                ComboBox::from_id_salt("display_mode")
+                    .selected_text(self.display_mode.name())
+                    .show_ui(ui, |ui| {
+                        for mode in DisplayMode::all() {
+                            ui.selectable_value(&mut self.display_mode, mode, mode.name());
+                        }
+                    });
+                let masks = &mut self.mask_settings;
+                ComboBox::from_id_salt("overlay_mode")
+                    .selected_text(masks.overlay.name())
+                    .show_ui(ui, |ui| {
+                        for mode in OverlayMode::ALL {
+                            ui.selectable_value(&mut masks.overlay, mode, mode.name());
+                        }
+                    });
+                if masks.overlay == OverlayMode::Confidence {
+                    ComboBox::from_id_salt("confidence_heatmap")
+                        .selected_text(masks.heatmap.name())
+                        .show_ui(ui, |ui| {
+                            for lut in ColorLut::ALL {
+                                ui.selectable_value(&mut masks.heatmap, lut, lut.name());
+                            }
+                        });
+                }
+                ComboBox::from_id_salt("color_palette")
+                    .selected_text(masks.palette.name())
+                    .show_ui(ui, |ui| {
+                        for palette in ColorPalette::ALL {
+                            ui.selectable_value(&mut masks.palette, palette, palette.name());
+                        }
+                    })
+                    .response
+                    .on_hover_text("Colors of new masks without class");
+                ui.checkbox(&mut masks.show_uncovered, "Show uncovered")
+                    .on_hover_text("Highlight pixels which belong to no mask");
+                if masks.overlay.has_fill() {
+                    ui.add(egui::Slider::new(&mut masks.default_opacity, 0..=255).text("Opacity"));
+                }
+                if masks.overlay.has_outline() {
+                    ui.add(
+                        egui::Slider::new(&mut masks.outline_width, 0.5..=10.0)
+                            .text("Outline width"),
+                    );
+                }
+            });
+        if !self.labels.is_empty() {
+            egui::CollapsingHeader::new("Labels")
+                .default_open(true)
+                .show(ui, |ui| {
+                    ui.label("Class of new masks:");
+                    self.labels
+                        .combo_box(ui, "active_class", &mut self.active_class);
+                });
+        }
+        egui::CollapsingHeader::new("Filters").show(ui, |ui| filters_ui(ui, &mut self.filters));
+        // Applied every frame, so newly loaded images use the selected display settings and labels too
+        self.state.image_state.set_display_mode(self.display_mode);
+        self.state.image_state.set_filters(&self.filters);
+        self.state
+            .image_state
+            .set_mask_settings(&self.mask_settings);
+        self.state
+            .image_state
+            .set_labels(&self.labels, self.active_class);
+
+        let ImageState::Loaded(image) = &mut self.state.image_state else {
+            ui.label("No image loaded");
+            return;
+        };
+        if !image.auxiliary.is_empty() {
+            egui::CollapsingHeader::new("Images")
+                .default_open(true)
+                .show(ui, |ui| auxiliary_ui(ui, image));
+        }
+        egui::CollapsingHeader::new("Layers").show(ui, |ui| {
+            self.layers.ui(ui, &mut image.masks);
+        });
+        egui::CollapsingHeader::new("Masks").show(ui, |ui| {
+            let mut add_as_part = image.masks.add_as_part();
+            ui.checkbox(&mut add_as_part, "Add as parts of selected")
+                .on_hover_text("New masks are clipped to the selected mask and become parts of it");
+            image.masks.set_add_as_part(add_as_part);
+            self.mask_edit.ui(ui);
+            masks_ui(ui, &self.labels, &mut image.masks, &self.mask_edit);
+        });
+        let mut next_gap = ui.input(|i| i.modifiers.command && i.key_pressed(egui::Key::G));
+        egui::CollapsingHeader::new("Gaps").show(ui, |ui| {
+            next_gap |= self.gaps.ui(ui);
+        });
+        if next_gap {
+            self.gaps.show_next(image, &mut self.state.viewer);
+        }
+        if let Some(layer) = image.masks.selected() {
+            let key = (image.id.clone(), image.masks.revision(), layer);
+            if self
+                .selected_geometry
+                .as_ref()
+                .is_none_or(|(cached, _)| *cached != key)
+                && let Some(Some(area)) = image.masks.areas().get(layer)
+            {
+                self.selected_geometry = Some((key, area.geometry()));
+            }
+            egui::CollapsingHeader::new("Selected mask")
+                .default_open(true)
+                .show(ui, |ui| {
+                    image.masks.area_info_ui(ui, layer);
+                    if let Some((_, geometry)) = &self.selected_geometry {
+                        geometry.ui(ui);
+                    }
+                });
+            if !self.attributes.is_empty() {
+                egui::CollapsingHeader::new("Attributes")
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        self.attribute_panel
+                            .ui(ui, &self.attributes, &mut image.masks);
+                    });
+            }
+        }
+        egui::CollapsingHeader::new("Histogram").show(ui, |ui| {
+            self.histogram.ui(ui, image);
+        });
+    }
+}
+
+fn auxiliary_ui(ui: &mut egui::Ui, image: &mut ImageStateLoaded) {
+    let mut base = image.base();
+    ui.radio_value(&mut base, None, "Primary");
+    for (idx, aux) in image.auxiliary.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut base, Some(idx), aux.name.as_str())
+                .on_hover_text("Show as base image");
+            ui.add_enabled(
+                base != Some(idx),
+                egui::Slider::new(&mut aux.opacity, 0.0..=1.0).text("opacity"),
+            );
+        });
+    }
+    image.set_base(base);
+}
+
+/// Finds uncovered regions one after another
+pub(super) struct GapFinder {
+    min_pixels: u64,
+    /// First pixel of the last shown gap
+    last: Option<[u32; 2]>,
+}
+
+impl Default for GapFinder {
+    fn default() -> Self {
+        Self {
+            min_pixels: 20,
+            last: None,
+        }
+    }
+}
+
+impl GapFinder {
+    /// Returns true if the next gap should be shown
+    fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::DragValue::new(&mut self.min_pixels)
+                    .range(1..=1_000_000)
+                    .prefix("min area: "),
+            );
+            ui.button("Next gap")
+                .on_hover_text("Show the next region without mask (Ctrl+G)")
+                .clicked()
+        })
+        .inner
+    }
+
+    fn show_next(&mut self, image: &ImageStateLoaded, viewer: &mut ImageViewer) {
+        let Some(gap) = image.masks.next_gap(self.last, self.min_pixels) else {
+            log::info!(
+                "No uncovered region with at least {} pixels",
+                self.min_pixels
+            );
+            self.last = None;
+            return;
+        };
+        self.last = Some(gap.first_pixel());
+        let roi = gap.roi();
+        let region = egui::Rect::from_min_size(
+            egui::pos2(roi.x as f32, roi.y as f32),
+            egui::vec2(roi.width.get() as f32, roi.height.get() as f32),
+        );
+        let [width, height] = image.masks.image_size().map(|x| x.get() as f32);
+        viewer.show_region(region, egui::vec2(width, height));
+    }
+}
+
+/// Parameters of the cleanups offered for every mask
+pub(super) struct MaskEditSettings {
+    element: StructuringElement,
+    connectivity: Connectivity,
+    min_area: u64,
+    /// Minimum confidence of pixels kept by "Binarize"
+    threshold: u8,
+}
+
+impl Default for MaskEditSettings {
+    fn default() -> Self {
+        Self {
+            element: StructuringElement::default(),
+            connectivity: Connectivity::default(),
+            min_area: 20,
+            threshold: 128,
+        }
+    }
+}
+
+impl MaskEditSettings {
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let mut radius = self.element.radius();
+            let mut square = matches!(self.element, StructuringElement::Square { .. });
+            ui.selectable_value(&mut square, false, "Disk");
+            ui.selectable_value(&mut square, true, "Square");
+            ui.add(
+                egui::DragValue::new(&mut radius)
+                    .range(1..=50)
+                    .prefix("r: "),
+            );
+            self.element = if square {
+                StructuringElement::Square { radius }
+            } else {
+                StructuringElement::Disk { radius }
+            };
+        });
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.connectivity, Connectivity::Four, "4-connected");
+            ui.selectable_value(&mut self.connectivity, Connectivity::Eight, "8-connected");
+            ui.add(
+                egui::DragValue::new(&mut self.min_area)
+                    .range(1..=1_000_000)
+                    .prefix("min area: "),
+            );
+        });
+        ui.add(egui::Slider::new(&mut self.threshold, 1..=u8::MAX).text("confidence threshold"));
+    }
+}
+
+/// Masks as a tree, parts are listed indented below the mask they are part of
+fn masks_ui(
+    ui: &mut egui::Ui,
+    labels: &LabelSchema,
+    masks: &mut MaskImage,
+    settings: &MaskEditSettings,
+) {
+    let len = masks.areas().len();
+    let mut children = vec![Vec::new(); len];
+    let mut roots = Vec::new();
+    for layer in (0..len).filter(|layer| masks.areas()[*layer].is_some()) {
+        match masks.parent_of(layer) {
+            Some(parent) => children[parent].push(layer),
+            None => roots.push(layer),
+        }
+    }
+    fn tree_ui(
+        ui: &mut egui::Ui,
+        labels: &LabelSchema,
+        masks: &mut MaskImage,
+        settings: &MaskEditSettings,
+        children: &[Vec<usize>],
+        layer: usize,
+    ) {
+        mask_ui(ui, labels, masks, settings, layer);
+        if !children[layer].is_empty() {
+            ui.indent(("mask_parts", layer), |ui| {
+                for child in &children[layer] {
+                    tree_ui(ui, labels, masks, settings, children, *child);
+                }
+            });
+        }
+    }
+    for layer in roots {
+        tree_ui(ui, labels, masks, settings, &children, layer);
+    }
+}
+
+fn mask_ui(
+    ui: &mut egui::Ui,
+    labels: &LabelSchema,
+    masks: &mut MaskImage,
+    settings: &MaskEditSettings,
+    layer: usize,
+) {
+    // Actions below can change or remove the area, so only copy what is shown
+    let Some(area) = masks.areas().get(layer).and_then(Option::as_ref) else {
+        return;
+    };
+    let ([r, g, b], class, kind) = (masks.area_color(area), area.class, area.kind);
+    ui.horizontal(|ui| {
+        let is_selected = masks.selected() == Some(layer);
+        let text = egui::RichText::new(format!("#{layer}")).color(egui::Color32::from_rgb(r, g, b));
+        if ui
+            .selectable_label(is_selected, text)
+            .on_hover_ui(|ui| {
+                if let Some(Some(area)) = masks.areas().get(layer) {
+                    area.geometry().ui(ui);
+                }
+            })
+            .clicked()
+        {
+            masks.set_selected((!is_selected).then_some(layer));
+        }
+        if !labels.is_empty() {
+            let mut class = class;
+            if labels.combo_box(ui, ("mask_class", layer), &mut class) {
+                masks.set_class(layer, class);
+            }
+        }
+        if ui.small_button("⏶").on_hover_text("Draw above").clicked() {
+            masks.move_area(layer, true);
+        }
+        if ui.small_button("⏷").on_hover_text("Draw below").clicked() {
+            masks.move_area(layer, false);
+        }
+        ui.menu_button("Edit", |ui| {
+            for op in MorphologyOp::ALL {
+                if ui.button(op.name()).clicked() {
+                    masks.apply_morphology(layer, op, settings.element);
+                    ui.close();
+                }
+            }
+            ui.separator();
+            let selected = masks.selected().filter(|selected| *selected != layer);
+            if ui
+                .add_enabled(selected.is_some(), egui::Button::new("Merge into selected"))
+                .clicked()
+                && let Some(selected) = selected
+            {
+                masks.merge_areas(selected, &[layer]);
+                ui.close();
+            }
+            if ui
+                .add_enabled(
+                    selected.is_some(),
+                    egui::Button::new("Make part of selected"),
+                )
+                .clicked()
+            {
+                masks.set_parent(layer, selected);
+                ui.close();
+            }
+            if ui
+                .add_enabled(
+                    masks.parent_of(layer).is_some(),
+                    egui::Button::new("Detach from parent"),
+                )
+                .clicked()
+            {
+                masks.set_parent(layer, None);
+                ui.close();
+            }
+            if ui.button("Split islands").clicked() {
+                masks.split_components(layer, settings.connectivity);
+                ui.close();
+            }
+            if ui.button("Fill holes").clicked() {
+                masks.replace_area(layer, |area, size| Some(area.fill_holes(size)));
+                ui.close();
+            }
+            if ui
+                .button("Binarize")
+                .on_hover_text("Keep only pixels above the confidence threshold")
+                .clicked()
+            {
+                masks.replace_area(layer, |area, _| area.binarize(settings.threshold));
+                ui.close();
+            }
+            if ui.button("Remove small islands").clicked() {
+                masks.replace_area(layer, |area, _| {
+                    area.remove_small_components(settings.min_area, settings.connectivity)
+                });
+                ui.close();
+            }
+            ui.separator();
+            let mut ignore = kind == AreaKind::Ignore;
+            if ui
+                .checkbox(&mut ignore, "Ignore region")
+                .on_hover_text("Neither background nor object, excluded from training")
+                .changed()
+            {
+                let kind = if ignore {
+                    AreaKind::Ignore
+                } else {
+                    AreaKind::Object
+                };
+                masks.set_kind(layer, kind);
+                ui.close();
+            }
+        });
+    });
+}
+
+fn filters_ui(ui: &mut egui::Ui, filters: &mut FilterChain) {
+    let mut remove = None;
+    for (idx, filter) in filters.0.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            if ui.small_button("🗙").clicked() {
+                remove = Some(idx);
+            }
+            ui.label(filter.name());
+            match filter {
+                ImageFilter::Gamma { gamma } => {
+                    ui.add(egui::DragValue::new(gamma).speed(0.01).range(0.1..=5.0));
+                }
+                ImageFilter::Clahe { tiles, clip_limit } => {
+                    ui.add(egui::DragValue::new(tiles).range(1..=32).prefix("tiles: "));
+                    ui.add(
+                        egui::DragValue::new(clip_limit)
+                            .speed(0.1)
+                            .range(1.0..=20.0)
+                            .prefix("clip: "),
+                    );
+                }
+                ImageFilter::Invert => {}
+                ImageFilter::UnsharpMask { radius, amount } => {
+                    ui.add(egui::DragValue::new(radius).range(1..=20).prefix("r: "));
+                    ui.add(
+                        egui::DragValue::new(amount)
+                            .speed(0.05)
+                            .range(0.0..=5.0)
+                            .prefix("amount: "),
+                    );
+                }
+                ImageFilter::MedianDenoise { radius } => {
+                    ui.add(egui::DragValue::new(radius).range(1..=5).prefix("r: "));
+                }
+            }
+        });
+    }
+    if let Some(idx) = remove {
+        filters.0.remove(idx);
+    }
+
+    ui.horizontal(|ui| {
+        ComboBox::from_id_salt("add_filter")
+            .selected_text("Add filter")
+            .show_ui(ui, |ui| {
+                for filter in ImageFilter::defaults() {
+                    if ui.selectable_label(false, filter.name()).clicked() {
+                        filters.0.push(filter);
+                    }
+                }
+            });
+        #[cfg(not(target_arch = "wasm32"))]
+        if ui.button("Save to config").clicked() {
+            if let Err(e) = super::native::save_filters(filters) {
+                log::error!("Failed to save filters: {e}");
+            }
+        }
+    });
+}
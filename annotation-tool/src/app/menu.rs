@@ -52,16 +52,64 @@ impl crate::app::ImageViewerApp {
                         masks.mark_not_dirty();
                         self.save_job = AsyncRefTask::new(
                             self.storage
-                                .store_masks(
-                                    id.clone(),
-                                    masks.subgroups().into_iter().flatten().collect(),
-                                )
+                                .store_masks(id.clone(), masks.compacted())
                                 .map(|x| x.map_err(|e| format!("Error during save: {e}")))
                                 .boxed(),
                         );
                     }
                 });
 
+                if ui
+                    .button("Export")
+                    .on_hover_text("Export masks as COCO json")
+                    .clicked()
+                {
+                    let file_name = std::path::Path::new(&**id)
+                        .file_name()
+                        .map(|x| x.to_string_lossy().to_string())
+                        .unwrap_or_default();
+                    let coco = crate::export::coco(
+                        &file_name,
+                        image.original.width().get(),
+                        image.original.height().get(),
+                        &masks.compacted(),
+                        &self.labels,
+                    );
+                    self.save_job = AsyncRefTask::new(
+                        self.storage
+                            .store_export(
+                                id.clone(),
+                                crate::storage::COCO_EXPORT,
+                                coco.to_string().into_bytes(),
+                            )
+                            .map(|x| x.map_err(|e| format!("Error during export: {e}")))
+                            .boxed(),
+                    );
+                }
+
+                if ui
+                    .button("Export label map")
+                    .on_hover_text(
+                        "Export the class id of every pixel as PNG, ignore regions are 255",
+                    )
+                    .clicked()
+                {
+                    let label_map = crate::export::label_map(
+                        image.original.width().get(),
+                        image.original.height().get(),
+                        &masks.compacted(),
+                    );
+                    self.save_job = match label_map {
+                        Ok(png) => AsyncRefTask::new(
+                            self.storage
+                                .store_export(id.clone(), crate::storage::LABEL_MAP_EXPORT, png)
+                                .map(|x| x.map_err(|e| format!("Error during export: {e}")))
+                                .boxed(),
+                        ),
+                        Err(e) => AsyncRefTask::new_ready(Err(format!("Error during export: {e}"))),
+                    };
+                }
+
                 if ui.button("Reset").clicked() {
                     masks.reset();
                 }
@@ -33,11 +33,34 @@ pub fn run_native(mappers: ImageCallbackMap) -> Result<(), eframe::Error> {
         "Image Viewer",
         options,
         Box::new(|_cc| {
-            Ok(Box::new(ImageViewerApp::new(
-                Box::new(crate::FileStorage::new(image_dir)),
-                Tools::from(&config),
-                super::MaskGenerator::new(mappers),
-            )))
+            Ok(Box::new(
+                ImageViewerApp::new(
+                    Box::new(crate::FileStorage::new(image_dir)),
+                    Tools::from(&config),
+                    super::MaskGenerator::new(mappers),
+                )
+                .with_filters(config.filters.clone())
+                .with_labels(config.labels.clone())
+                .with_attributes(config.attributes.clone())
+                .with_mask_settings(config.masks.clone()),
+            ))
         }),
     )
 }
+
+/// Stores `filters` in config.json. Other entries of the file are kept as they are.
+pub(super) fn save_filters(filters: &imanot::FilterChain) -> io::Result<()> {
+    let mut config = match std::fs::File::open("config.json") {
+        Ok(f) => serde_json::from_reader(f)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => serde_json::json!({}),
+        Err(e) => return Err(e),
+    };
+    let serde_json::Value::Object(entries) = &mut config else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "config.json is not an object",
+        ));
+    };
+    entries.insert("filters".into(), serde_json::to_value(filters)?);
+    std::fs::write("config.json", serde_json::to_string_pretty(&config)?)
+}
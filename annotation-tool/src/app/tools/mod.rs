@@ -1,4 +1,7 @@
-use imanot::{ClearTool, ImageLoadOk, PanTool, RectTool, ToolFactory};
+use imanot::{
+    AreaKind, ClearTool, ConfidenceBrushTool, ImageLoadOk, PanTool, RectTool, SplitTool,
+    ToolFactory,
+};
 
 #[cfg(feature = "sam")]
 mod sam;
@@ -11,10 +14,24 @@ pub fn default_tools(config: &crate::config::Config) -> ToolFactories {
     let session = sam::SamSession::new(&config.sam_path).unwrap();
     vec![
         ("Clear".to_string(), ClearTool::create_factory()),
+        (
+            "Confidence".to_string(),
+            ConfidenceBrushTool::create_factory(),
+        ),
+        (
+            "Ignore".to_string(),
+            RectTool::create_factory_with(|tool| {
+                tool.set_kind(AreaKind::Ignore);
+            }),
+        ),
         ("Pan".to_string(), PanTool::create_factory()),
         #[cfg(feature = "sam")]
-        ("SAM".to_string(), sam::SamTool::create_factory(session)),
+        (
+            "SAM".to_string(),
+            sam::SamTool::create_factory(session, config.sam_input.clone(), config.sam_threshold),
+        ),
         ("Rect".to_string(), RectTool::create_factory()),
+        ("Split".to_string(), SplitTool::create_factory()),
     ]
 }
 
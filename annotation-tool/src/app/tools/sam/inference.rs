@@ -4,7 +4,8 @@ use std::{
 };
 
 use image::{DynamicImage, GenericImageView, Rgba, imageops::FilterType};
-use imanot::{CreateTotal, MetaRange};
+use imanot::{Meta, MetaRange};
+use imask::NonZeroRange;
 use itertools::Itertools;
 use ndarray::{Array, ArrayBase, Dim, IxDyn, IxDynImpl, OwnedRepr};
 
@@ -100,31 +101,43 @@ pub(super) fn prepare_image_input(
     })
 }
 
+/// Probabilities are quantized to this many levels, so neighbouring pixels share a range
+const CONFIDENCE_LEVELS: f32 = 32.0;
+
+/// Pixels of the mask logits with a probability above `threshold`. Their probability is kept as
+/// confidence of the [`Meta`].
 pub(super) fn extract_pixel_ranges(
     iter: impl Iterator<Item = f32>,
     width: NonZeroU32,
+    threshold: f32,
 ) -> Vec<MetaRange> {
-    let mut result = vec![];
-    iter.enumerate()
-        .filter_map(|(pos, item)| (item > 0.0).then_some(pos as u64))
-        .chunk_by(|&x| x / NonZeroU64::from(width))
-        .into_iter()
-        .for_each(|(_, mut b)| {
-            let first = b.next().expect("Doesn't yield if group is empty");
-            result.push(MetaRange::new_total(first, NonZeroU64::MIN));
-            b.fold(first, |last, x| {
-                if x - 1 == last {
-                    let item = result.last_mut().unwrap();
-                    item.range.increment_length();
-                } else {
-                    result.push(MetaRange::new_total(x, NonZeroU64::MIN));
-                }
-                x
-            });
-        });
+    let width = NonZeroU64::from(width);
+    let mut result: Vec<MetaRange> = vec![];
+    for (pos, logit) in iter.enumerate() {
+        let probability = 1.0 / (1.0 + (-logit).exp());
+        if probability <= threshold {
+            continue;
+        }
+        let pos = pos as u64;
+        let meta = Meta::new(quantize(probability));
+        match result.last_mut() {
+            // Ranges don't continue in the next row
+            Some(last) if last.range.end == pos && last.meta == meta && pos % width != 0 => {
+                last.range.increment_length()
+            }
+            _ => result.push(MetaRange {
+                range: NonZeroRange::from_span(pos, NonZeroU64::MIN),
+                meta,
+            }),
+        }
+    }
     result
 }
 
+fn quantize(probability: f32) -> u8 {
+    ((probability * CONFIDENCE_LEVELS).ceil() / CONFIDENCE_LEVELS * 255.0) as u8
+}
+
 #[derive(Debug)]
 pub struct ResizedImageData<T> {
     pub(super) image_data: T,
@@ -150,6 +163,8 @@ impl<T> ResizedImageData<T> {
 mod tests {
     use std::num::NonZero;
 
+    use imanot::CreateTotal;
+
     use super::*;
 
     const NON_ZERO_3: NonZero<u32> = NonZero::new(3).unwrap();
@@ -158,7 +173,21 @@ mod tests {
     fn extract_pixel_ranges_summarizes_pixels() {
         assert_eq!(
             vec![MetaRange::new_total(0, NON_ZERO_3.into())],
-            extract_pixel_ranges([1., 1., 1.].iter().copied(), NON_ZERO_3)
+            extract_pixel_ranges([10., 10., 10.].iter().copied(), NON_ZERO_3, 0.5)
+        );
+    }
+
+    #[test]
+    fn extract_pixel_ranges_keeps_probability() {
+        let logits = [0.5, 0.5, 0.5, 0.5, -1.0, 10.0];
+        let ranges = extract_pixel_ranges(logits.iter().copied(), NON_ZERO_3, 0.3);
+        assert_eq!(
+            ranges
+                .iter()
+                .map(|r| (r.range.start, r.range.end, r.meta.confidence()))
+                .collect::<Vec<_>>(),
+            // The second row starts a new range, sigmoid(-1) is below the threshold
+            vec![(0, 3, 159), (3, 4, 159), (5, 6, 255)]
         );
     }
 }
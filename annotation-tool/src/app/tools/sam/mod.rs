@@ -13,7 +13,12 @@ pub use native_ort::SamSession;
 type RgbImageInterleaved<T> = imbuf::Image<[T; 3], 1>;
 
 pub struct SamTool {
-    embeddings: AsyncRefTask<Result<SamEmbeddings, InferenceError>>,
+    // None until the image named by `input` is available
+    embeddings: Option<AsyncRefTask<Result<SamEmbeddings, InferenceError>>>,
+    // Auxiliary image which should be used, None for the primary image
+    input: Option<String>,
+    // Minimum probability of mask pixels
+    threshold: f32,
     session: SamSession,
     rect_selection: RectSelection,
     // If selection starts, before embeddings are ready
@@ -21,17 +26,38 @@ pub struct SamTool {
 }
 
 impl SamTool {
-    pub fn new(session: SamSession, img: Image<[u8; 3], 1>) -> Self {
+    /// `img` is the primary image. Embeddings of an auxiliary `input` are computed on the first
+    /// interaction, when the auxiliary images are available.
+    pub fn new(
+        session: SamSession,
+        img: Image<[u8; 3], 1>,
+        input: Option<String>,
+        threshold: f32,
+    ) -> Self {
+        let embeddings = input
+            .is_none()
+            .then(|| AsyncRefTask::new(session.get_image_embeddings(img).boxed()));
         Self {
-            embeddings: AsyncRefTask::new(session.get_image_embeddings(img).boxed()),
+            embeddings,
+            input,
+            threshold,
             session,
             rect_selection: RectSelection::default(),
             last_pos: None,
         }
     }
-    pub fn create_factory(session: SamSession) -> ToolFactory {
+    pub fn create_factory(
+        session: SamSession,
+        input: Option<String>,
+        threshold: f32,
+    ) -> ToolFactory {
         Box::new(move |img| {
-            let tool = SamTool::new(session.clone(), img.adjust.clone());
+            let tool = SamTool::new(
+                session.clone(),
+                img.adjust.clone(),
+                input.clone(),
+                threshold,
+            );
             async move { Ok(Box::new(tool) as Box<dyn Tool>) }.boxed_local()
         })
     }
@@ -39,12 +65,25 @@ impl SamTool {
 
 impl Tool for SamTool {
     fn handle_interaction(&mut self, mut ctx: ToolContext) {
+        if self.embeddings.is_none() {
+            // Falls back to the primary image if the auxiliary image is missing
+            let img = ctx
+                .image
+                .image_by_name(self.input.as_deref())
+                .unwrap_or(&ctx.image.image);
+            self.embeddings = Some(AsyncRefTask::new(
+                self.session
+                    .get_image_embeddings(img.adjust.clone())
+                    .boxed(),
+            ));
+        }
         if let Some(rect_result) = self.rect_selection.drag_finished(&mut ctx) {
             self.last_pos = Some(rect_result.bounds());
         }
-        if let (Some([[top_x, top_y], [bottom_x, bottom_y]]), Some(Ok(loaded_embeddings))) =
-            (self.last_pos, self.embeddings.data())
-        {
+        if let (Some([[top_x, top_y], [bottom_x, bottom_y]]), Some(Ok(loaded_embeddings))) = (
+            self.last_pos,
+            self.embeddings.as_mut().and_then(|e| e.data()),
+        ) {
             let new_mask = self
                 .session
                 .decode_prompt(
@@ -53,14 +92,18 @@ impl Tool for SamTool {
                     bottom_x as f32,
                     bottom_y as f32,
                     loaded_embeddings,
+                    self.threshold,
                 )
                 .unwrap();
 
             let width = ctx.image.image.original.width();
             let height = ctx.image.image.original.height();
-            let color = ctx.image.masks.next_color();
-            if let Some(pixel_area) = PixelArea::new(new_mask.with_bounds(width, height), color) {
-                ctx.image.masks.add_area_non_overlapping_parts(pixel_area);
+            if let Some(mut pixel_area) =
+                PixelArea::new(new_mask.with_bounds(width, height), [0, 0, 0])
+            {
+                pixel_area.color = ctx.image.masks.color_for(&pixel_area);
+                let mode = ctx.add_mode();
+                ctx.image.masks.add_area_at(pixel_area, None, mode);
             }
             self.last_pos = None;
 
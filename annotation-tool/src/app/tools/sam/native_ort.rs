@@ -80,6 +80,7 @@ impl SamSession {
         x2: f32,
         y2: f32,
         embeddings: &SamEmbeddings,
+        threshold: f32,
     ) -> Result<Vec<MetaRange>, InferenceError> {
         // Prepare input for decoder
 
@@ -161,6 +162,7 @@ impl SamSession {
         Ok(extract_pixel_ranges(
             pixel_view.iter().copied(),
             embeddings.original_width,
+            threshold,
         ))
     }
 }
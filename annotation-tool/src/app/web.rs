@@ -29,11 +29,16 @@ pub fn run_web(mappers: ImageCallbackMap) {
                 canvas.clone(),
                 web_options,
                 Box::new(|_cc| {
+                    let config = Config::default();
                     let mut app = crate::app::ImageViewerApp::new(
                         Box::new(crate::InMemoryStorage::chessboard()),
-                        Tools::from(&Config::default()),
+                        Tools::from(&config),
                         super::MaskGenerator::new(mappers),
-                    );
+                    )
+                    .with_filters(config.filters)
+                    .with_labels(config.labels)
+                    .with_attributes(config.attributes)
+                    .with_mask_settings(config.masks);
                     app.state.cursor_image.enable_web(canvas);
                     Ok(Box::new(app))
                 }),
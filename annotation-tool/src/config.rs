@@ -4,7 +4,22 @@ use std::path::PathBuf;
 #[serde(default)]
 pub struct Config {
     pub sam_path: PathBuf,
+    /// Name of the auxiliary image SAM runs on. Uses the primary image if unset or missing
+    pub sam_input: Option<String>,
+    /// Minimum probability of pixels in SAM masks, in 0..1. Only set here, not in the UI. The
+    /// quantized probability is kept as confidence, so a lower value keeps uncertain pixels,
+    /// which "Binarize" can remove later.
+    #[serde(deserialize_with = "probability")]
+    pub sam_threshold: f32,
     pub image_dir: Option<PathBuf>,
+    /// Filters computing the displayed image from the original image
+    pub filters: imanot::FilterChain,
+    /// Label classes which can be assigned to masks
+    pub labels: imanot::LabelSchema,
+    /// Attributes such as "occluded" which can be set for every mask
+    pub attributes: imanot::AttributeSchema,
+    /// How masks are drawn over the image
+    pub masks: imanot::MaskSettings,
     pub(crate) egui: crate::app::Config,
 }
 
@@ -12,8 +27,38 @@ impl Default for Config {
     fn default() -> Self {
         Self {
             sam_path: "sam".into(),
+            sam_input: None,
+            sam_threshold: 0.5,
             image_dir: None,
+            filters: Default::default(),
+            labels: Default::default(),
+            attributes: Default::default(),
+            masks: Default::default(),
             egui: Default::default(),
         }
     }
 }
+
+fn probability<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+    let value = <f32 as serde::Deserialize>::deserialize(deserializer)?;
+    if (0.0..1.0).contains(&value) {
+        Ok(value)
+    } else {
+        Err(serde::de::Error::custom(format!(
+            "probability {value} is not in 0..1"
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sam_threshold_is_a_probability() {
+        let config: Config = serde_json::from_str(r#"{"sam_threshold": 0.3}"#).unwrap();
+        assert_eq!(config.sam_threshold, 0.3);
+        assert!(serde_json::from_str::<Config>(r#"{"sam_threshold": 1.5}"#).is_err());
+        assert!(serde_json::from_str::<Config>(r#"{"sam_threshold": -0.1}"#).is_err());
+    }
+}
@@ -0,0 +1,238 @@
+//! COCO style and label map export of the masks of one image.
+//! Besides the standard fields, every COCO annotation carries the `geometry` computed by imanot
+//! the `parent_id` of the annotation it is a part of and its `attributes` as a JSON object.
+//! Ignore regions are crowd annotations in COCO and [`IGNORE_VALUE`] in label maps.
+//! Masks without a known class get the extra COCO category "unlabeled", or "ignore" for
+//! ignore regions, with ids after the highest class id.
+
+use imanot::{AreaKind, AttributeValue, LabelSchema, PixelArea};
+use serde_json::{Value, json};
+
+/// Label map value of ignore regions
+pub(crate) const IGNORE_VALUE: u8 = 255;
+
+pub(crate) fn coco(
+    file_name: &str,
+    width: u32,
+    height: u32,
+    masks: &[PixelArea],
+    labels: &LabelSchema,
+) -> Value {
+    let mut categories = labels
+        .classes()
+        .iter()
+        .map(|class| {
+            json!({
+                "id": class.id.0,
+                "name": class.name,
+                "supercategory": class.parent.and_then(|p| labels.get(p)).map(|p| &p.name),
+            })
+        })
+        .collect::<Vec<_>>();
+    let next_id = labels
+        .classes()
+        .iter()
+        .map(|class| class.id.0 as u32 + 1)
+        .max()
+        .unwrap_or(1);
+    let (unlabeled_id, ignore_id) = (next_id, next_id + 1);
+    let category_id = |mask: &PixelArea| match mask.class.filter(|c| labels.get(*c).is_some()) {
+        Some(class) => class.0 as u32,
+        None if mask.kind == AreaKind::Ignore => ignore_id,
+        None => unlabeled_id,
+    };
+    for (id, name) in [(unlabeled_id, "unlabeled"), (ignore_id, "ignore")] {
+        if masks.iter().any(|mask| category_id(mask) == id) {
+            categories.push(json!({ "id": id, "name": name, "supercategory": null }));
+        }
+    }
+    let annotations = masks
+        .iter()
+        .enumerate()
+        .map(|(idx, mask)| {
+            let geometry = mask.geometry();
+            let bbox = geometry.bounding_box;
+            let ignore = (mask.kind == AreaKind::Ignore) as u8;
+            json!({
+                "id": idx + 1,
+                "image_id": 1,
+                "category_id": category_id(mask),
+                "parent_id": mask.parent.map(|parent| parent + 1),
+                "segmentation": {
+                    "size": [height, width],
+                    "counts": column_major_rle(mask, width, height),
+                },
+                "area": geometry.area,
+                "bbox": [bbox.min_x, bbox.min_y, bbox.width(), bbox.height()],
+                "iscrowd": ignore,
+                "ignore": ignore,
+                "geometry": geometry,
+                "attributes": attributes(mask),
+            })
+        })
+        .collect::<Vec<_>>();
+    json!({
+        "images": [{ "id": 1, "file_name": file_name, "width": width, "height": height }],
+        "categories": categories,
+        "annotations": annotations,
+    })
+}
+
+/// Booleans as JSON booleans, text and enum options as strings
+fn attributes(mask: &PixelArea) -> Value {
+    mask.attributes
+        .iter()
+        .map(|(name, value)| {
+            let value = match value {
+                AttributeValue::Bool(b) => Value::Bool(*b),
+                AttributeValue::Text(text) => Value::String(text.clone()),
+            };
+            (name.clone(), value)
+        })
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+/// Grayscale PNG with the class id of every pixel. Later masks are drawn above earlier ones and
+/// ignore regions above all masks. Background, unlabeled masks and classes with ids of
+/// [`IGNORE_VALUE`] or above are 0.
+pub(crate) fn label_map(
+    width: u32,
+    height: u32,
+    masks: &[PixelArea],
+) -> image::ImageResult<Vec<u8>> {
+    let image = image::GrayImage::from_raw(width, height, label_values(width, height, masks))
+        .expect("Buffer has width * height pixels");
+    let mut png = std::io::Cursor::new(Vec::new());
+    image.write_to(&mut png, image::ImageFormat::Png)?;
+    Ok(png.into_inner())
+}
+
+fn label_values(width: u32, height: u32, masks: &[PixelArea]) -> Vec<u8> {
+    let mut values = vec![0; width as usize * height as usize];
+    let objects = masks.iter().filter(|mask| mask.kind == AreaKind::Object);
+    let ignored = masks.iter().filter(|mask| mask.kind == AreaKind::Ignore);
+    for mask in objects.chain(ignored) {
+        let value = match mask.kind {
+            AreaKind::Ignore => IGNORE_VALUE,
+            AreaKind::Object => match mask.class.and_then(|c| u8::try_from(c.0).ok()) {
+                Some(id) if id < IGNORE_VALUE => id,
+                _ => continue,
+            },
+        };
+        for (y, x_range, _) in mask.row_runs() {
+            let row_start = y as usize * width as usize;
+            values[row_start + x_range.start as usize..row_start + x_range.end as usize]
+                .fill(value);
+        }
+    }
+    values
+}
+
+/// Uncompressed COCO RLE: Alternating counts of background and foreground pixels in
+/// column-major order, starting with background
+fn column_major_rle(mask: &PixelArea, width: u32, height: u32) -> Vec<u64> {
+    let (width, height) = (width as u64, height as u64);
+    let mut positions = mask
+        .row_runs()
+        .flat_map(|(y, x_range, _)| x_range.map(move |x| x * height + y))
+        .collect::<Vec<_>>();
+    positions.sort_unstable();
+
+    let mut counts = Vec::new();
+    let mut next = 0;
+    for pos in positions {
+        if pos == next && !counts.is_empty() {
+            *counts.last_mut().expect("Checked above") += 1;
+        } else {
+            counts.extend([pos - next, 1]);
+        }
+        next = pos + 1;
+    }
+    counts.push(width * height - next);
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::{NonZeroU32, NonZeroU64};
+
+    use imanot::CreateTotal;
+    use imask::ImaskSet;
+
+    use super::*;
+
+    #[test]
+    fn rle_is_column_major() {
+        // 3x2 image, pixels (1, 0), (2, 0) and (1, 1)
+        let width = NonZeroU32::new(3).unwrap();
+        let height = NonZeroU32::new(2).unwrap();
+        let mask = PixelArea::with_black_color(
+            [
+                imanot::MetaRange::new_total(1, NonZeroU64::new(2).unwrap()),
+                imanot::MetaRange::new_total(4, NonZeroU64::MIN),
+            ]
+            .with_bounds(width, height),
+        )
+        .unwrap();
+        // Column-major: (0,0) (0,1) | (1,0) (1,1) | (2,0) (2,1)
+        assert_eq!(column_major_rle(&mask, 3, 2), vec![2, 3, 1]);
+    }
+
+    #[test]
+    fn masks_without_class_get_extra_categories() {
+        let width = NonZeroU32::new(4).unwrap();
+        let mask = || PixelArea::single_pixel_total_color(0, 0, width, [0, 0, 0], width);
+        let labels = LabelSchema::new(vec![imanot::LabelClass {
+            id: imanot::ClassId(3),
+            name: "car".into(),
+            color: [1, 2, 3],
+            hotkey: None,
+            parent: None,
+        }])
+        .unwrap();
+        let masks = [
+            mask().with_class(Some(imanot::ClassId(3))),
+            mask(),
+            mask().with_kind(AreaKind::Ignore),
+        ];
+        let coco = coco("image.png", 4, 1, &masks, &labels);
+        let ids = |list: &str, field: &str| {
+            coco[list]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|x| x[field].as_u64())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids("categories", "id"), [Some(3), Some(4), Some(5)]);
+        assert_eq!(
+            ids("annotations", "category_id"),
+            [Some(3), Some(4), Some(5)]
+        );
+        assert_eq!(coco["categories"][2]["name"], "ignore");
+    }
+
+    #[test]
+    fn ignore_regions_are_on_top_of_label_map() {
+        // 4x1 image: class 3 at 0..3, ignore region at 2..4
+        let width = NonZeroU32::new(4).unwrap();
+        let height = NonZeroU32::MIN;
+        let range = |start, len| {
+            PixelArea::with_black_color(
+                [imanot::MetaRange::new_total(
+                    start,
+                    NonZeroU64::new(len).unwrap(),
+                )]
+                .with_bounds(width, height),
+            )
+            .unwrap()
+        };
+        let ignore = range(2, 2).with_kind(AreaKind::Ignore);
+        let object = range(0, 3).with_class(Some(imanot::ClassId(3)));
+        assert_eq!(
+            label_values(4, 1, &[ignore, object]),
+            vec![3, 3, IGNORE_VALUE, IGNORE_VALUE]
+        );
+    }
+}
@@ -1,5 +1,6 @@
 mod app;
 mod config;
+mod export;
 mod storage;
 
 #[cfg(not(target_arch = "wasm32"))]
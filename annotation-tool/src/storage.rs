@@ -11,13 +11,41 @@ pub mod file;
 pub mod in_memory;
 
 const PREAMBLE: [u8; 5] = [b'a', b'n', b'n', b'o', b't'];
-const VERSION: u16 = 1;
+/// Version 2 stores the properties of every mask before its ranges: the class id as u16,
+/// `u16::MAX` for unlabeled masks, and the region of interest (x, y, width, height) as u32, which
+/// positions are relative to. Then the layer settings: the name as u16 length and UTF-8 bytes,
+/// the opacity as u8 and flags as u8 (1: hidden, 2: locked). Then the RGB color, the kind as u8
+/// (0: object, 1: ignore region) and the position of the parent mask as u32, `u32::MAX` for none.
+/// Then the attributes: their count as u16, then per attribute the name as u16 length and UTF-8
+/// bytes, a type as u8 and the value. Booleans are a u8, text is a u16 length and UTF-8 bytes.
+/// The lengths of ranges are u32 and followed by the confidence of every range as u8.
+const VERSION: u16 = 2;
+const LAYER_HIDDEN: u8 = 1;
+const LAYER_LOCKED: u8 = 2;
+const KIND_OBJECT: u8 = 0;
+const KIND_IGNORE: u8 = 1;
+const UNLABELED: u16 = imanot::ClassId::RESERVED.0;
+const NO_PARENT: u32 = u32::MAX;
+const ATTRIBUTE_BOOL: u8 = 0;
+const ATTRIBUTE_TEXT: u8 = 1;
+
+/// Extension of COCO exports, see [`Storage::store_export`]
+pub const COCO_EXPORT: &str = "coco.json";
+/// Extension of label map exports. Like all exports, they are neither listed nor auxiliary images.
+pub const LABEL_MAP_EXPORT: &str = "labels.png";
 
 pub trait Storage {
     fn list_images(&self) -> BoxFuture<'static, std::io::Result<Vec<ImageListTaskItem>>>;
     fn load_image(&self, id: &ImageId) -> BoxFuture<'static, std::io::Result<ImageData>>;
     fn store_masks(&self, id: ImageId, masks: Vec<PixelArea>)
     -> BoxFuture<'static, io::Result<()>>;
+    /// Stores `contents` as `{stem}.{extension}` next to the image
+    fn store_export(
+        &self,
+        id: ImageId,
+        extension: &str,
+        contents: Vec<u8>,
+    ) -> BoxFuture<'static, io::Result<()>>;
 }
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Debug)]
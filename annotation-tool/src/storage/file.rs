@@ -1,7 +1,7 @@
 use std::{
     fs::DirEntry,
     io::{self, ErrorKind, Read, Write},
-    num::NonZeroU16,
+    num::NonZeroU32,
     ops::Range,
     path::PathBuf,
     str::FromStr,
@@ -9,13 +9,32 @@ use std::{
 
 use futures::{FutureExt, future::BoxFuture};
 use imanot::{
-    CreateTotal, ImageData, ImageId, ImageListTaskItem, MetaRange, PixelArea, load_image,
+    AreaKind, AttributeValue, Attributes, AuxiliaryImage, ClassId, ImageData, ImageId,
+    ImageListTaskItem, LayerSettings, Meta, MetaRange, PixelArea, load_image,
 };
-use imask::ImaskSet;
+use imask::NonZeroRange;
 use itertools::Itertools;
-use log::info;
+use log::{info, warn};
 
-use super::{Kind, MaybeOneOrMany, PREAMBLE, Storage, VERSION};
+use super::{
+    ATTRIBUTE_BOOL, ATTRIBUTE_TEXT, COCO_EXPORT, KIND_IGNORE, KIND_OBJECT, Kind, LABEL_MAP_EXPORT,
+    LAYER_HIDDEN, LAYER_LOCKED, MaybeOneOrMany, NO_PARENT, PREAMBLE, Storage, UNLABELED, VERSION,
+};
+
+/// Extensions of files written by [`Storage::store_export`]
+const EXPORTS: [&str; 2] = [COCO_EXPORT, LABEL_MAP_EXPORT];
+
+/// Whether `path` is `{stem}.{extension}` of an export
+fn is_export(path: &std::path::Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| {
+            EXPORTS.iter().any(|export| {
+                name.strip_suffix(export)
+                    .is_some_and(|stem| stem.ends_with('.'))
+            })
+        })
+}
 
 pub struct FileStorage {
     base: String,
@@ -26,10 +45,13 @@ impl FileStorage {
     }
 
     fn list_images_blocking(path: PathBuf) -> std::io::Result<Vec<ImageListTaskItem>> {
-        Ok(visit_directory_files(path)
+        let items = visit_directory_files(path)
             .filter_map(|x| {
                 let x = x.ok()?;
                 let path = x.path();
+                if is_export(&path) {
+                    return None;
+                }
                 let kind = path
                     .extension()?
                     .to_str()
@@ -66,13 +88,88 @@ impl FileStorage {
                     }),
                 }
             })
-            .collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+
+        // `{stem}.{name}.{ext}` is a auxiliary image of `{stem}.{ext}`, if the latter exists
+        let names = items
+            .iter()
+            .map(|x| x.name.clone())
+            .collect::<std::collections::HashSet<_>>();
+        Ok(items
+            .into_iter()
+            .filter(|x| {
+                x.name
+                    .rsplit_once('.')
+                    .is_none_or(|(stem, _)| !names.contains(stem))
+            })
+            .collect())
+    }
+
+    fn load_auxiliary_images(
+        id: &ImageId,
+        width: NonZeroU32,
+        height: NonZeroU32,
+    ) -> std::io::Result<Vec<AuxiliaryImage>> {
+        // Broken auxiliary images are skipped, so they never prevent loading the primary image
+        let file_path = std::path::Path::new(&**id);
+        let (Some(stem), Some(dir)) = (
+            file_path.file_stem().and_then(|x| x.to_str()),
+            file_path.parent(),
+        ) else {
+            return Ok(Vec::new());
+        };
+        let prefix = format!("{stem}.");
+        let mut result = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = match entry {
+                Ok(entry) => entry.path(),
+                Err(e) => {
+                    warn!("Skip an entry while looking for auxiliary images of {id:?}: {e}");
+                    continue;
+                }
+            };
+            let Some(name) = path
+                .file_stem()
+                .and_then(|x| x.to_str())
+                .and_then(|x| x.strip_prefix(&prefix))
+            else {
+                continue;
+            };
+            let is_image = path
+                .extension()
+                .and_then(|x| x.to_str())
+                .is_some_and(|x| Kind::from_str(x) == Ok(Kind::Image));
+            if !is_image || name.contains('.') || is_export(&path) {
+                continue;
+            }
+            let image = match std::fs::read(&path).and_then(|bytes| load_image(&bytes)) {
+                Ok(image) => image,
+                Err(e) => {
+                    warn!("Ignore auxiliary image {path:?}, as it can't be loaded: {e}");
+                    continue;
+                }
+            };
+            if (image.original.width(), image.original.height()) != (width, height) {
+                warn!("Ignore auxiliary image {path:?}, as its size differs from {id:?}");
+                continue;
+            }
+            result.push(AuxiliaryImage {
+                name: name.to_string(),
+                image,
+            });
+        }
+        result.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+        Ok(result)
     }
     fn get_image_path(&self) -> PathBuf {
         self.base.as_str().into()
     }
 
     fn get_mask_path(id: &ImageId) -> std::io::Result<PathBuf> {
+        Self::get_sibling_path(id, "masks")
+    }
+
+    fn get_sibling_path(id: &ImageId, extension: &str) -> std::io::Result<PathBuf> {
         let file_path = std::path::Path::new(&**id);
 
         let filename = file_path
@@ -83,7 +180,7 @@ impl FileStorage {
             .parent()
             .ok_or_else(|| std::io::Error::other("Base musten't be a root-dir"))?;
 
-        Ok(images_path.join(format!("{filename}.masks")))
+        Ok(images_path.join(format!("{filename}.{extension}")))
     }
 }
 
@@ -115,68 +212,18 @@ impl Storage for FileStorage {
             let image_width = image_load_ok.original.width();
             let image_height = image_load_ok.original.height();
             let masks = match std::fs::File::open(mask_path) {
-                Ok(mut f) => {
-                    let mut preamble = [0; PREAMBLE.len()];
-                    f.read_exact(&mut preamble)?;
-                    if preamble != PREAMBLE {
-                        return Err(std::io::Error::new(
-                            std::io::ErrorKind::InvalidInput,
-                            "Invalid preamble",
-                        ));
-                    }
-                    let mut version_bytes = [0; 2];
-                    f.read_exact(&mut version_bytes)?;
-                    assert_eq!(VERSION, u16::from_le_bytes(version_bytes));
-
-                    let mut f = brotli::Decompressor::new(f, 4096);
-                    let mut pixel_range_bytes = [0; 2];
-                    let mut all = Vec::new();
-                    let mut starts = Vec::<u32>::new();
-                    let mut lens = Vec::new();
-
-                    while f.read_exact(&mut pixel_range_bytes).is_ok() {
-                        let pixel_range_len = u16::from_le_bytes(pixel_range_bytes) as usize;
-                        if pixel_range_len == 0 {
-                            continue;
-                        }
-
-                        starts.resize(pixel_range_len, 0);
-                        lens.resize(pixel_range_len, 0);
-                        f.read_exact(bytemuck::cast_slice_mut(&mut starts))?;
-                        f.read_exact(bytemuck::cast_slice_mut(&mut lens))?;
-                        // Generate color based on current position (simulating the seed)
-                        let color = imanot::random_color_from_seed(all.len() as u16);
-
-                        all.push(
-                            PixelArea::new(
-                                starts
-                                    .iter()
-                                    .zip(lens.iter())
-                                    .map(|(start, len)| match NonZeroU16::try_from(*len) {
-                                        Ok(l) => Ok(MetaRange::new_total(*start as _, l.into())),
-                                        Err(e) => Err(std::io::Error::new(
-                                            ErrorKind::InvalidData,
-                                            format!("position {start},{len}: {e:?}"),
-                                        )),
-                                    })
-                                    .collect::<Result<Vec<_>, _>>()?
-                                    .with_bounds(image_width, image_height),
-                                color,
-                            )
-                            .expect("Group cannot be empty, checked in loop"),
-                        );
-                    }
-
-                    all
-                }
+                Ok(f) => read_masks(f, image_width, image_height)?,
                 Err(e) if e.kind() == std::io::ErrorKind::NotFound => Default::default(),
                 Err(e) => return Err(e),
             };
 
+            let auxiliary = Self::load_auxiliary_images(&id, image_width, image_height)?;
+
             Ok(ImageData {
                 id,
                 masks,
                 image: image_load_ok,
+                auxiliary,
             })
         }
         .boxed()
@@ -199,39 +246,359 @@ impl Storage for FileStorage {
                     Err(e) => return Err(e),
                 }
             } else {
-                let mut f = std::fs::File::create(path)?;
-                f.write_all(&PREAMBLE)?;
-                f.write_all(&VERSION.to_le_bytes())?;
-
-                let mut f = brotli::CompressorWriter::new(f, 4096, 11, 22);
-                for sub in masks {
-                    if sub.range_len() > u16::MAX as _ {
-                        return Err(std::io::Error::new(
+                write_masks(std::fs::File::create(path)?, &masks)?;
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn store_export(
+        &self,
+        id: ImageId,
+        extension: &str,
+        contents: Vec<u8>,
+    ) -> BoxFuture<'static, io::Result<()>> {
+        let path = Self::get_sibling_path(&id, extension);
+        async move {
+            let path = path?;
+            info!("Export to: {path:?}");
+            std::fs::write(path, contents)
+        }
+        .boxed()
+    }
+}
+
+/// Reads a mask file, including preamble and version
+fn read_masks(
+    mut f: impl Read,
+    image_width: NonZeroU32,
+    image_height: NonZeroU32,
+) -> io::Result<Vec<PixelArea>> {
+    let mut preamble = [0; PREAMBLE.len()];
+    f.read_exact(&mut preamble)?;
+    if preamble != PREAMBLE {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "Invalid preamble",
+        ));
+    }
+    let mut version_bytes = [0; 2];
+    f.read_exact(&mut version_bytes)?;
+    let version = u16::from_le_bytes(version_bytes);
+    if !(1..=VERSION).contains(&version) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Unsupported mask file version {version}"),
+        ));
+    }
+
+    let mut f = brotli::Decompressor::new(f, 4096);
+    let mut class_bytes = [0; 2];
+    let mut roi_bytes = [0u32; 4];
+    let mut pixel_range_bytes = [0; 2];
+    let mut all = Vec::new();
+    let mut starts = Vec::<u32>::new();
+    let mut lens = Vec::<u32>::new();
+    let mut confidences = Vec::new();
+
+    loop {
+        let class = if version >= 2 {
+            if f.read_exact(&mut class_bytes).is_err() {
+                break;
+            }
+            match u16::from_le_bytes(class_bytes) {
+                UNLABELED => None,
+                id => Some(ClassId(id)),
+            }
+        } else {
+            None
+        };
+        let roi = if version >= 2 {
+            f.read_exact(bytemuck::cast_slice_mut(&mut roi_bytes))?;
+            let [x, y, width, height] = roi_bytes.map(u32::from_le);
+            match (NonZeroU32::new(width), NonZeroU32::new(height)) {
+                (Some(width), Some(height)) => imask::Rect::new(x, y, width, height),
+                _ => {
+                    return Err(std::io::Error::new(
+                        ErrorKind::InvalidData,
+                        format!("Empty region of interest {width}x{height}"),
+                    ));
+                }
+            }
+        } else {
+            imask::Rect::new(0, 0, image_width, image_height)
+        };
+        let layer = if version >= 2 {
+            read_layer_settings(&mut f)?
+        } else {
+            LayerSettings::default()
+        };
+        let color = if version >= 2 {
+            let mut color = [0; 3];
+            f.read_exact(&mut color)?;
+            color
+        } else {
+            // Generate color based on current position (simulating the seed)
+            imanot::random_color_from_seed(all.len() as u16)
+        };
+        let kind = if version >= 2 {
+            read_kind(&mut f)?
+        } else {
+            AreaKind::Object
+        };
+        let parent = if version >= 2 {
+            let mut parent_bytes = [0; 4];
+            f.read_exact(&mut parent_bytes)?;
+            match u32::from_le_bytes(parent_bytes) {
+                NO_PARENT => None,
+                parent => Some(parent as usize),
+            }
+        } else {
+            None
+        };
+        let attributes = if version >= 2 {
+            read_attributes(&mut f)?
+        } else {
+            Attributes::new()
+        };
+        if f.read_exact(&mut pixel_range_bytes).is_err() {
+            break;
+        }
+        let pixel_range_len = u16::from_le_bytes(pixel_range_bytes) as usize;
+        if pixel_range_len == 0 {
+            continue;
+        }
+
+        starts.resize(pixel_range_len, 0);
+        f.read_exact(bytemuck::cast_slice_mut(&mut starts))?;
+        lens.clear();
+        confidences.clear();
+        if version >= 2 {
+            lens.resize(pixel_range_len, 0);
+            f.read_exact(bytemuck::cast_slice_mut(&mut lens))?;
+            confidences.resize(pixel_range_len, 0);
+            f.read_exact(&mut confidences)?;
+        } else {
+            let mut short_lens = vec![0u16; pixel_range_len];
+            f.read_exact(bytemuck::cast_slice_mut(&mut short_lens))?;
+            lens.extend(short_lens.into_iter().map(u32::from));
+            confidences.resize(pixel_range_len, u8::MAX);
+        }
+
+        all.push(
+            PixelArea::from_roi(
+                starts
+                    .iter()
+                    .zip(lens.iter())
+                    .zip(confidences.iter())
+                    .map(|((start, len), confidence)| match NonZeroU32::new(*len) {
+                        Some(l) => Ok(MetaRange {
+                            range: NonZeroRange::from_span(*start as _, l.into()),
+                            meta: Meta::new(*confidence),
+                        }),
+                        None => Err(std::io::Error::new(
                             ErrorKind::InvalidData,
-                            format!(
-                                "Version1 allows for MAX {} subgroups, got {}",
-                                u16::MAX,
-                                sub.range_len()
-                            ),
-                        ));
-                    }
-                    let sub_len = sub.range_len() as u16;
+                            format!("Empty range at position {start}"),
+                        )),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?,
+                roi,
+                color,
+            )
+            .expect("Group cannot be empty, checked in loop")
+            .with_class(class)
+            .with_layer(layer)
+            .with_kind(kind)
+            .with_parent(parent)
+            .with_attributes(attributes),
+        );
+    }
+    detach_invalid_parents(&mut all);
+    Ok(all)
+}
 
-                    f.write_all(&sub_len.to_le_bytes())?;
-                    for (subgroup, _) in sub.pixels.iter::<Range<u32>>() {
-                        f.write_all(&subgroup.start.to_le_bytes())?;
-                    }
-                    for (subgroup, _) in sub.pixels.iter::<Range<u32>>() {
-                        f.write_all(&u16::try_from(subgroup.len()).unwrap().to_le_bytes())?;
-                    }
+/// Makes masks top level masks, whose parent doesn't exist or whose hierarchy is cyclic
+fn detach_invalid_parents(masks: &mut [PixelArea]) {
+    for layer in 0..masks.len() {
+        let mut visited = vec![layer];
+        let mut current = layer;
+        while let Some(parent) = masks[current].parent {
+            if parent >= masks.len() || visited.contains(&parent) {
+                warn!("Mask {current} has an invalid parent {parent}, it becomes a top level mask");
+                masks[current].parent = None;
+                break;
+            }
+            visited.push(parent);
+            current = parent;
+        }
+    }
+}
+
+/// Writes a mask file, including preamble and version
+fn write_masks(mut f: impl Write, masks: &[PixelArea]) -> io::Result<()> {
+    f.write_all(&PREAMBLE)?;
+    f.write_all(&VERSION.to_le_bytes())?;
+
+    let mut f = brotli::CompressorWriter::new(f, 4096, 11, 22);
+    for sub in masks {
+        let sub_len = u16::try_from(sub.range_len()).map_err(|_| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "A mask may have at most {} ranges, got {}",
+                    u16::MAX,
+                    sub.range_len()
+                ),
+            )
+        })?;
+
+        let class = sub.class.map_or(UNLABELED, |c| c.0);
+        f.write_all(&class.to_le_bytes())?;
+        let roi = sub.roi();
+        for value in [roi.x, roi.y, roi.width.get(), roi.height.get()] {
+            f.write_all(&value.to_le_bytes())?;
+        }
+        write_layer_settings(&mut f, &sub.layer)?;
+        f.write_all(&sub.color)?;
+        f.write_all(&[match sub.kind {
+            AreaKind::Object => KIND_OBJECT,
+            AreaKind::Ignore => KIND_IGNORE,
+        }])?;
+        let parent = sub.parent.map_or(NO_PARENT, |parent| parent as u32);
+        f.write_all(&parent.to_le_bytes())?;
+        write_attributes(&mut f, &sub.attributes)?;
+        f.write_all(&sub_len.to_le_bytes())?;
+        for (subgroup, _) in sub.pixels.iter::<Range<u32>>() {
+            f.write_all(&subgroup.start.to_le_bytes())?;
+        }
+        for (subgroup, _) in sub.pixels.iter::<Range<u32>>() {
+            let len = subgroup.end.checked_sub(subgroup.start).ok_or_else(|| {
+                io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Invalid range {subgroup:?}"),
+                )
+            })?;
+            f.write_all(&len.to_le_bytes())?;
+        }
+        for (_, meta) in sub.pixels.iter::<Range<u32>>() {
+            f.write_all(&[meta.confidence()])?;
+        }
+    }
+    f.flush()
+}
+
+fn read_layer_settings(f: &mut impl Read) -> io::Result<LayerSettings> {
+    let name = read_string(f)?;
+    let mut opacity_and_flags = [0; 2];
+    f.read_exact(&mut opacity_and_flags)?;
+    let [opacity, flags] = opacity_and_flags;
+    Ok(LayerSettings {
+        name,
+        visible: flags & LAYER_HIDDEN == 0,
+        opacity,
+        locked: flags & LAYER_LOCKED != 0,
+    })
+}
+
+fn read_kind(f: &mut impl Read) -> io::Result<AreaKind> {
+    let mut kind = [0];
+    f.read_exact(&mut kind)?;
+    match kind[0] {
+        KIND_OBJECT => Ok(AreaKind::Object),
+        KIND_IGNORE => Ok(AreaKind::Ignore),
+        other => Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!("Unknown area kind {other}"),
+        )),
+    }
+}
+
+fn read_string(f: &mut impl Read) -> io::Result<String> {
+    let mut len_bytes = [0; 2];
+    f.read_exact(&mut len_bytes)?;
+    let mut bytes = vec![0; u16::from_le_bytes(len_bytes) as usize];
+    f.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+}
+
+fn read_attributes(f: &mut impl Read) -> io::Result<Attributes> {
+    let mut count_bytes = [0; 2];
+    f.read_exact(&mut count_bytes)?;
+    (0..u16::from_le_bytes(count_bytes))
+        .map(|_| {
+            let name = read_string(f)?;
+            let mut kind = [0];
+            f.read_exact(&mut kind)?;
+            let value = match kind[0] {
+                ATTRIBUTE_BOOL => {
+                    let mut value = [0];
+                    f.read_exact(&mut value)?;
+                    AttributeValue::Bool(value[0] != 0)
                 }
+                ATTRIBUTE_TEXT => AttributeValue::Text(read_string(f)?),
+                other => {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidData,
+                        format!("Unknown attribute type {other}"),
+                    ));
+                }
+            };
+            Ok((name, value))
+        })
+        .collect()
+}
+
+fn write_string(f: &mut impl Write, text: &str) -> io::Result<()> {
+    let len = u16::try_from(text.len()).map_err(|_| {
+        io::Error::new(
+            ErrorKind::InvalidData,
+            format!("Attribute text is longer than {} bytes", u16::MAX),
+        )
+    })?;
+    f.write_all(&len.to_le_bytes())?;
+    f.write_all(text.as_bytes())
+}
 
-                f.flush()?;
+fn write_attributes(f: &mut impl Write, attributes: &Attributes) -> io::Result<()> {
+    let count = u16::try_from(attributes.len()).map_err(|_| {
+        io::Error::new(
+            ErrorKind::InvalidData,
+            format!("More than {} attributes", u16::MAX),
+        )
+    })?;
+    f.write_all(&count.to_le_bytes())?;
+    for (name, value) in attributes {
+        write_string(f, name)?;
+        match value {
+            AttributeValue::Bool(value) => f.write_all(&[ATTRIBUTE_BOOL, *value as u8])?,
+            AttributeValue::Text(text) => {
+                f.write_all(&[ATTRIBUTE_TEXT])?;
+                write_string(f, text)?;
             }
-            Ok(())
         }
-        .boxed()
     }
+    Ok(())
+}
+
+fn write_layer_settings(f: &mut impl Write, layer: &LayerSettings) -> io::Result<()> {
+    let name = layer.name.as_bytes();
+    let name_len = u16::try_from(name.len()).map_err(|_| {
+        io::Error::new(
+            ErrorKind::InvalidData,
+            format!("Layer name is longer than {} bytes", u16::MAX),
+        )
+    })?;
+    f.write_all(&name_len.to_le_bytes())?;
+    f.write_all(name)?;
+    let mut flags = 0;
+    if !layer.visible {
+        flags |= LAYER_HIDDEN;
+    }
+    if layer.locked {
+        flags |= LAYER_LOCKED;
+    }
+    f.write_all(&[layer.opacity, flags])
 }
 
 pub fn visit_directory_files(
@@ -257,3 +624,93 @@ pub fn visit_directory_files(
     }
     one_level(path.into())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn masks_with_long_ranges_round_trip() {
+        // A filled 256x256 mask is a single range of 65536 pixels
+        let size = NonZeroU32::new(256).unwrap();
+        let len = NonZeroU32::new(256 * 256).unwrap();
+        let mask = PixelArea::single_pixel_total_color(0, 0, len, [1, 2, 3], size)
+            .with_class(Some(ClassId(4)));
+        assert_eq!(mask.range_len(), 1);
+        let mut bytes = Vec::new();
+        write_masks(&mut bytes, std::slice::from_ref(&mask)).unwrap();
+        assert_eq!(
+            read_masks(bytes.as_slice(), size, size).unwrap(),
+            vec![mask]
+        );
+    }
+
+    #[test]
+    fn invalid_parents_are_detached() {
+        let size = NonZeroU32::new(10).unwrap();
+        let mask = |parent| {
+            PixelArea::single_pixel_total_color(0, 0, NonZeroU32::MIN, [1, 2, 3], size)
+                .with_parent(parent)
+        };
+        // 0 and 1 are each other's parent, 2 is part of 1 and 3 is part of a missing mask
+        let mut bytes = Vec::new();
+        write_masks(
+            &mut bytes,
+            &[mask(Some(1)), mask(Some(0)), mask(Some(1)), mask(Some(7))],
+        )
+        .unwrap();
+        let parents = read_masks(bytes.as_slice(), size, size)
+            .unwrap()
+            .into_iter()
+            .map(|mask| mask.parent)
+            .collect::<Vec<_>>();
+        assert_eq!(parents, [Some(1), None, Some(1), None]);
+    }
+
+    #[test]
+    fn version_1_masks_are_read() {
+        let mut bytes = PREAMBLE.to_vec();
+        bytes.extend(1u16.to_le_bytes());
+        {
+            let mut f = brotli::CompressorWriter::new(&mut bytes, 4096, 11, 22);
+            f.write_all(&1u16.to_le_bytes()).unwrap();
+            f.write_all(&12u32.to_le_bytes()).unwrap();
+            f.write_all(&3u16.to_le_bytes()).unwrap();
+        }
+        let size = NonZeroU32::new(10).unwrap();
+        let masks = read_masks(bytes.as_slice(), size, size).unwrap();
+        assert_eq!(masks.len(), 1);
+        assert_eq!(
+            (masks[0].first_pixel(), masks[0].pixel_count()),
+            ([2, 1], 3)
+        );
+        assert_eq!(masks[0].mean_confidence(), 1.0);
+        assert_eq!(masks[0].color, imanot::random_color_from_seed(0));
+    }
+
+    #[test]
+    fn exports_are_no_images() {
+        let dir =
+            std::env::temp_dir().join(format!("annotation-tool-exports-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["a.png", "a.depth.png", "a.labels.png"] {
+            image::RgbImage::new(2, 2).save(dir.join(name)).unwrap();
+        }
+        let images = FileStorage::list_images_blocking(dir.clone()).unwrap();
+        let size = NonZeroU32::new(2).unwrap();
+        let auxiliary = FileStorage::load_auxiliary_images(&images[0].id, size, size).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            images.iter().map(|x| x.name.as_str()).collect::<Vec<_>>(),
+            ["a"]
+        );
+        assert_eq!(
+            auxiliary
+                .iter()
+                .map(|x| x.name.as_str())
+                .collect::<Vec<_>>(),
+            ["depth"]
+        );
+    }
+}
@@ -74,4 +74,17 @@ impl Storage for InMemoryStorage {
         }
         .boxed()
     }
+
+    fn store_export(
+        &self,
+        id: ImageId,
+        extension: &str,
+        _contents: Vec<u8>,
+    ) -> BoxFuture<'static, io::Result<()>> {
+        std::future::ready(Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("Export of {id:?} as {extension} is not supported in memory"),
+        )))
+        .boxed()
+    }
 }
@@ -0,0 +1,268 @@
+//! Free-form attributes of areas besides their class, e.g. "occluded" or a comment.
+
+use std::collections::{BTreeMap, HashSet};
+
+use egui::ComboBox;
+
+use crate::MaskImage;
+
+/// Values of an area by attribute name. Unset attributes are missing.
+pub type Attributes = BTreeMap<String, AttributeValue>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AttributeValue {
+    Bool(bool),
+    /// Free text or the selected option of an enum attribute
+    Text(String),
+}
+
+/// Values an attribute can take
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
+pub enum AttributeKind {
+    Bool,
+    Enum { options: Vec<String> },
+    Text,
+}
+
+/// An attribute areas can have, e.g. "truncated" as bool
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct AttributeDef {
+    pub name: String,
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub kind: AttributeKind,
+}
+
+impl AttributeDef {
+    /// Whether `value` can be stored for this attribute
+    pub fn accepts(&self, value: &AttributeValue) -> bool {
+        match (&self.kind, value) {
+            (AttributeKind::Bool, AttributeValue::Bool(_)) => true,
+            (AttributeKind::Enum { options }, AttributeValue::Text(text)) => options.contains(text),
+            (AttributeKind::Text, AttributeValue::Text(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum AttributeSchemaError {
+    #[error("Attribute '{0}' is defined more than once")]
+    DuplicateName(String),
+    #[error("Enum attribute '{0}' has no options")]
+    NoOptions(String),
+}
+
+/// Validated list of attributes. Names are unique and enums have at least one option.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(try_from = "Vec<AttributeDef>", into = "Vec<AttributeDef>")
+)]
+pub struct AttributeSchema {
+    attributes: Vec<AttributeDef>,
+}
+
+impl TryFrom<Vec<AttributeDef>> for AttributeSchema {
+    type Error = AttributeSchemaError;
+
+    fn try_from(attributes: Vec<AttributeDef>) -> Result<Self, Self::Error> {
+        Self::new(attributes)
+    }
+}
+
+impl From<AttributeSchema> for Vec<AttributeDef> {
+    fn from(schema: AttributeSchema) -> Self {
+        schema.attributes
+    }
+}
+
+impl AttributeSchema {
+    pub fn new(attributes: Vec<AttributeDef>) -> Result<Self, AttributeSchemaError> {
+        let mut names = HashSet::new();
+        for attribute in &attributes {
+            if !names.insert(attribute.name.as_str()) {
+                return Err(AttributeSchemaError::DuplicateName(attribute.name.clone()));
+            }
+            if let AttributeKind::Enum { options } = &attribute.kind
+                && options.is_empty()
+            {
+                return Err(AttributeSchemaError::NoOptions(attribute.name.clone()));
+            }
+        }
+        Ok(Self { attributes })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.attributes.is_empty()
+    }
+
+    pub fn attributes(&self) -> &[AttributeDef] {
+        &self.attributes
+    }
+
+    pub fn get(&self, name: &str) -> Option<&AttributeDef> {
+        self.attributes.iter().find(|a| a.name == name)
+    }
+}
+
+/// Editor of the attributes of the selected area
+#[derive(Default)]
+pub struct AttributePanel {
+    /// Layer with a text attribute being typed and the [`MaskImage::revision`] typing started at.
+    /// Applied when the text field loses focus or another area is selected. This way, typing a
+    /// comment adds a single history entry.
+    editing: Option<(usize, u64, Attributes)>,
+}
+
+impl AttributePanel {
+    pub fn ui(&mut self, ui: &mut egui::Ui, schema: &AttributeSchema, masks: &mut MaskImage) {
+        let selected = masks.selected();
+        // After an edit, undo or redo the layer may hold another area, so the text is dropped.
+        // Otherwise text typed before the selection changed belongs to the previous selection.
+        if self.editing.as_ref().is_some_and(|(editing, revision, _)| {
+            *revision != masks.revision() || Some(*editing) != selected
+        }) && let Some((editing, revision, attributes)) = self.editing.take()
+            && revision == masks.revision()
+        {
+            masks.set_attributes(editing, attributes);
+        }
+        let Some(layer) = selected else {
+            return;
+        };
+        let Some(stored) = masks
+            .areas()
+            .get(layer)
+            .and_then(Option::as_ref)
+            .map(|area| area.attributes.clone())
+        else {
+            return;
+        };
+        let mut attributes = match &self.editing {
+            Some((editing, _, attributes)) if *editing == layer => attributes.clone(),
+            _ => stored,
+        };
+        let (mut commit, mut typing) = (false, false);
+        egui::Grid::new("attributes").num_columns(2).show(ui, |ui| {
+            for attribute in schema.attributes() {
+                ui.label(&attribute.name);
+                let name = &attribute.name;
+                match &attribute.kind {
+                    AttributeKind::Bool => {
+                        let mut checked = attributes.get(name) == Some(&AttributeValue::Bool(true));
+                        if ui.checkbox(&mut checked, "").changed() {
+                            set(
+                                &mut attributes,
+                                name,
+                                checked.then_some(AttributeValue::Bool(true)),
+                            );
+                            commit = true;
+                        }
+                    }
+                    AttributeKind::Enum { options } => {
+                        let mut selected = match attributes.get(name) {
+                            Some(AttributeValue::Text(text)) => Some(text.clone()),
+                            _ => None,
+                        };
+                        let before = selected.clone();
+                        ComboBox::from_id_salt(("attribute", name))
+                            .selected_text(selected.as_deref().unwrap_or("—"))
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut selected, None, "—");
+                                for option in options {
+                                    ui.selectable_value(
+                                        &mut selected,
+                                        Some(option.clone()),
+                                        option,
+                                    );
+                                }
+                            });
+                        if selected != before {
+                            set(&mut attributes, name, selected.map(AttributeValue::Text));
+                            commit = true;
+                        }
+                    }
+                    AttributeKind::Text => {
+                        let mut text = match attributes.get(name) {
+                            Some(AttributeValue::Text(text)) => text.clone(),
+                            _ => String::new(),
+                        };
+                        let response = ui.text_edit_singleline(&mut text);
+                        if response.changed() {
+                            let value = (!text.is_empty()).then_some(AttributeValue::Text(text));
+                            set(&mut attributes, name, value);
+                            typing = true;
+                        }
+                        commit |= response.lost_focus();
+                    }
+                }
+                ui.end_row();
+            }
+        });
+        if commit {
+            self.editing = None;
+            masks.set_attributes(layer, attributes);
+        } else if typing {
+            // Pending text of another revision was dropped above, so this is the starting one
+            self.editing = Some((layer, masks.revision(), attributes));
+        }
+    }
+}
+
+fn set(attributes: &mut Attributes, name: &str, value: Option<AttributeValue>) {
+    match value {
+        Some(value) => attributes.insert(name.to_owned(), value),
+        None => attributes.remove(name),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attribute(name: &str, kind: AttributeKind) -> AttributeDef {
+        AttributeDef {
+            name: name.into(),
+            kind,
+        }
+    }
+
+    #[test]
+    fn invalid_schemas() {
+        assert_eq!(
+            AttributeSchema::new(vec![
+                attribute("occluded", AttributeKind::Bool),
+                attribute("occluded", AttributeKind::Text),
+            ]),
+            Err(AttributeSchemaError::DuplicateName("occluded".into()))
+        );
+        assert_eq!(
+            AttributeSchema::new(vec![attribute(
+                "quality",
+                AttributeKind::Enum { options: vec![] }
+            )]),
+            Err(AttributeSchemaError::NoOptions("quality".into()))
+        );
+    }
+
+    #[test]
+    fn schema_from_config() {
+        let schema: AttributeSchema = serde_json::from_str(
+            r#"[
+                {"name": "occluded", "type": "bool"},
+                {"name": "quality", "type": "enum", "options": ["good", "bad"]},
+                {"name": "comment", "type": "text"}
+            ]"#,
+        )
+        .unwrap();
+        let quality = schema.get("quality").unwrap();
+        assert!(quality.accepts(&AttributeValue::Text("bad".into())));
+        assert!(!quality.accepts(&AttributeValue::Text("ugly".into())));
+        assert!(!quality.accepts(&AttributeValue::Bool(true)));
+        assert_eq!(schema.get("comment").unwrap().kind, AttributeKind::Text);
+        assert!(schema.get("truncated").is_none());
+    }
+}
@@ -0,0 +1,171 @@
+use egui::Color32;
+
+/// False-color lookup table for grayscale data
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub enum ColorLut {
+    Viridis,
+    Magma,
+    Jet,
+}
+
+impl ColorLut {
+    pub const ALL: [Self; 3] = [Self::Viridis, Self::Magma, Self::Jet];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorLut::Viridis => "Viridis",
+            ColorLut::Magma => "Magma",
+            ColorLut::Jet => "Jet",
+        }
+    }
+
+    /// Color for every possible 8-bit intensity
+    pub fn table(self) -> [[u8; 3]; 256] {
+        std::array::from_fn(|i| {
+            let t = i as f32 / 255.0;
+            let rgb = match self {
+                // Polynomial approximations of the matplotlib colormaps
+                ColorLut::Viridis => polynomial(
+                    t,
+                    [
+                        [0.277_727_3, 0.005_407_345, 0.334_099_8],
+                        [0.105_093_04, 1.404_613_5, 1.384_590_2],
+                        [-0.330_861_83, 0.214_847_56, 0.095_095_16],
+                        [-4.634_230_5, -5.799_101, -19.332_441],
+                        [6.228_27, 14.179_933, 56.690_553],
+                        [4.776_385, -13.745_145, -65.353_035],
+                        [-5.435_456, 4.645_852_6, 26.312_435],
+                    ],
+                ),
+                ColorLut::Magma => polynomial(
+                    t,
+                    [
+                        [-0.002_136_485, -0.000_749_655, -0.005_386_128],
+                        [0.251_660_54, 0.677_523_2, 2.494_026_6],
+                        [8.353_717, -3.577_719_5, 0.314_467_9],
+                        [-27.668_733, 14.264_731, -13.649_213],
+                        [52.176_14, -27.943_606, 12.944_169],
+                        [-50.768_524, 29.046_583, 4.234_153],
+                        [18.655_705, -11.489_774, -5.601_961_5],
+                    ],
+                ),
+                ColorLut::Jet => [
+                    1.5 - (4.0 * t - 3.0).abs(),
+                    1.5 - (4.0 * t - 2.0).abs(),
+                    1.5 - (4.0 * t - 1.0).abs(),
+                ],
+            };
+            rgb.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+        })
+    }
+}
+
+fn polynomial(t: f32, coefficients: [[f32; 3]; 7]) -> [f32; 3] {
+    std::array::from_fn(|channel| {
+        coefficients
+            .iter()
+            .rev()
+            .fold(0.0, |acc, c| acc * t + c[channel])
+    })
+}
+
+/// How the adjusted image is rendered. Masks are not affected.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub enum DisplayMode {
+    #[default]
+    Rgb,
+    Red,
+    Green,
+    Blue,
+    Luminance,
+    /// Luminance mapped through a false-color lookup table
+    FalseColor(ColorLut),
+}
+
+impl DisplayMode {
+    pub fn all() -> impl Iterator<Item = Self> {
+        [
+            Self::Rgb,
+            Self::Red,
+            Self::Green,
+            Self::Blue,
+            Self::Luminance,
+        ]
+        .into_iter()
+        .chain(ColorLut::ALL.map(Self::FalseColor))
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DisplayMode::Rgb => "RGB",
+            DisplayMode::Red => "Red",
+            DisplayMode::Green => "Green",
+            DisplayMode::Blue => "Blue",
+            DisplayMode::Luminance => "Luminance",
+            DisplayMode::FalseColor(lut) => lut.name(),
+        }
+    }
+
+    /// Returns a function converting adjusted pixels into texture colors
+    pub fn mapper(self) -> impl Fn([u8; 3]) -> Color32 {
+        let lut = match self {
+            DisplayMode::FalseColor(lut) => Some(lut.table()),
+            _ => None,
+        };
+        move |[r, g, b]| {
+            let gray = Color32::from_gray;
+            match (self, &lut) {
+                (DisplayMode::Rgb, _) => Color32::from_rgb(r, g, b),
+                (DisplayMode::Red, _) => gray(r),
+                (DisplayMode::Green, _) => gray(g),
+                (DisplayMode::Blue, _) => gray(b),
+                (DisplayMode::Luminance, _) => gray(luminance([r, g, b])),
+                (DisplayMode::FalseColor(_), Some(lut)) => {
+                    let [r, g, b] = lut[luminance([r, g, b]) as usize];
+                    Color32::from_rgb(r, g, b)
+                }
+                (DisplayMode::FalseColor(_), None) => unreachable!("Lut is built for FalseColor"),
+            }
+        }
+    }
+}
+
+fn luminance([r, g, b]: [u8; 3]) -> u8 {
+    ((299 * r as u32 + 587 * g as u32 + 114 * b as u32 + 500) / 1000) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_channel_modes_are_gray() {
+        let pixel = [10, 20, 30];
+        assert_eq!(
+            DisplayMode::Rgb.mapper()(pixel),
+            Color32::from_rgb(10, 20, 30)
+        );
+        assert_eq!(DisplayMode::Red.mapper()(pixel), Color32::from_gray(10));
+        assert_eq!(DisplayMode::Green.mapper()(pixel), Color32::from_gray(20));
+        assert_eq!(DisplayMode::Blue.mapper()(pixel), Color32::from_gray(30));
+        assert_eq!(
+            DisplayMode::Luminance.mapper()(pixel),
+            Color32::from_gray(18)
+        );
+    }
+
+    #[test]
+    fn lut_endpoints() {
+        let close = |a: [u8; 3], b: [u8; 3]| a.iter().zip(b).all(|(a, b)| a.abs_diff(b) <= 1);
+        let viridis = ColorLut::Viridis.table();
+        assert!(close(viridis[0], [71, 1, 85]), "{:?}", viridis[0]);
+        assert!(close(viridis[255], [252, 231, 33]), "{:?}", viridis[255]);
+        let jet = ColorLut::Jet.table();
+        assert_eq!(jet[0], [0, 0, 128]);
+        assert_eq!(jet[255], [128, 0, 0]);
+        let magma = ColorLut::Magma.table();
+        assert_eq!(magma[0], [0, 0, 0]);
+    }
+}
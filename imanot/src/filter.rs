@@ -0,0 +1,408 @@
+//! Filters which compute `ImageLoadOk::adjust` from the `OriginalImage`.
+//! All filters work on normalized f32 channels (0.0..=1.0), so 16-bit images keep their precision until the end.
+
+use std::num::NonZeroU32;
+use std::ops::RangeInclusive;
+
+use futures::FutureExt;
+use imbuf::Image;
+
+use crate::{AsyncTask, Histogram, OriginalImage, RgbImageInterleaved};
+
+/// Computation of a filtered image, see [`FilterChain::apply_async`]
+pub type FilterTask = AsyncTask<Result<Image<[u8; 3], 1>, String>>;
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
+pub enum ImageFilter {
+    /// `value^gamma`: Values below 1.0 brighten, values above 1.0 darken the image
+    Gamma {
+        gamma: f32,
+    },
+    /// Contrast limited adaptive histogram equalization on a grid of `tiles` x `tiles`.
+    /// `clip_limit` is relative to the average bin count of a tile
+    Clahe {
+        tiles: u32,
+        clip_limit: f32,
+    },
+    Invert,
+    /// Adds `amount` times the difference to a box-blurred image with the given radius
+    UnsharpMask {
+        radius: u32,
+        amount: f32,
+    },
+    /// Median of the (2 * radius + 1)² neighbourhood
+    MedianDenoise {
+        radius: u32,
+    },
+}
+
+impl ImageFilter {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ImageFilter::Gamma { .. } => "Gamma",
+            ImageFilter::Clahe { .. } => "CLAHE",
+            ImageFilter::Invert => "Invert",
+            ImageFilter::UnsharpMask { .. } => "Unsharp mask",
+            ImageFilter::MedianDenoise { .. } => "Median denoise",
+        }
+    }
+
+    /// One instance of every filter with reasonable default parameters
+    pub fn defaults() -> [Self; 5] {
+        [
+            ImageFilter::Gamma { gamma: 1.0 },
+            ImageFilter::Clahe {
+                tiles: 8,
+                clip_limit: 2.0,
+            },
+            ImageFilter::Invert,
+            ImageFilter::UnsharpMask {
+                radius: 2,
+                amount: 1.0,
+            },
+            ImageFilter::MedianDenoise { radius: 1 },
+        ]
+    }
+
+    fn apply(&self, planes: &mut Planes) {
+        let (width, height) = (planes.width, planes.height);
+        for plane in &mut planes.channels {
+            match *self {
+                ImageFilter::Gamma { gamma } => plane.iter_mut().for_each(|v| *v = v.powf(gamma)),
+                ImageFilter::Clahe { tiles, clip_limit } => {
+                    clahe(plane, width, height, tiles as usize, clip_limit)
+                }
+                ImageFilter::Invert => plane.iter_mut().for_each(|v| *v = 1.0 - *v),
+                ImageFilter::UnsharpMask { radius, amount } => {
+                    let blurred = box_blur(plane, width, height, radius as usize);
+                    for (v, b) in plane.iter_mut().zip(blurred) {
+                        *v = (*v + amount * (*v - b)).clamp(0.0, 1.0);
+                    }
+                }
+                ImageFilter::MedianDenoise { radius } => {
+                    *plane = median(plane, width, height, radius as usize)
+                }
+            }
+        }
+    }
+}
+
+/// Filters applied in order on top of the contrast stretched original image.
+/// The empty chain reproduces the `adjust` image created while loading.
+/// Only the primary image is filtered, auxiliary images are shown as loaded.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct FilterChain(pub Vec<ImageFilter>);
+
+impl FilterChain {
+    pub fn apply(&self, original: &OriginalImage) -> Image<[u8; 3], 1> {
+        let mut planes = Planes::from_original(original);
+        for filter in &self.0 {
+            filter.apply(&mut planes);
+        }
+        planes.into_rgb(original.width(), original.height())
+    }
+
+    /// Range of original values of gray images drawn from black to white, where `histogram`
+    /// is one of `original`. Display modes only pick channels, so they keep this range.
+    /// `None` for color images, which are not stretched, and for chains with CLAHE, which
+    /// remaps values depending on their neighbourhood.
+    pub fn display_window(
+        &self,
+        original: &OriginalImage,
+        histogram: &Histogram,
+    ) -> Option<RangeInclusive<u32>> {
+        let is_gray = matches!(original, OriginalImage::Luma8(_) | OriginalImage::Luma16(_));
+        let has_clahe = self
+            .0
+            .iter()
+            .any(|filter| matches!(filter, ImageFilter::Clahe { .. }));
+        if !is_gray || has_clahe {
+            return None;
+        }
+        let [lower, upper] = stretch_ranks(histogram.statistics().count as usize)
+            .map(|rank| histogram.value_at_rank(rank as u64));
+        Some(lower..=upper)
+    }
+
+    /// Applies the chain on a background thread. On wasm, it is computed when the task is polled.
+    /// Fails if the computation panicked.
+    pub fn apply_async(&self, original: OriginalImage) -> FilterTask {
+        let chain = self.clone();
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let (tx, rx) = futures::channel::oneshot::channel();
+            std::thread::spawn(move || tx.send(chain.apply(&original)));
+            AsyncTask::new(
+                async move { rx.await.map_err(|_| "Filter thread panicked".to_string()) }
+                    .boxed_local(),
+            )
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            AsyncTask::new(async move { Ok(chain.apply(&original)) }.boxed_local())
+        }
+    }
+}
+
+struct Planes {
+    width: usize,
+    height: usize,
+    channels: Vec<Vec<f32>>,
+}
+
+impl Planes {
+    fn from_original(original: &OriginalImage) -> Self {
+        let width = original.width().get() as usize;
+        let height = original.height().get() as usize;
+        let channels = match original {
+            OriginalImage::Luma8(img) => {
+                vec![stretch(
+                    img.buffer().iter().map(|v| *v as f32 / 255.0).collect(),
+                )]
+            }
+            OriginalImage::Luma16(img) => {
+                vec![stretch(
+                    img.buffer().iter().map(|v| *v as f32 / 65535.0).collect(),
+                )]
+            }
+            OriginalImage::Rgb8(img) => split_channels(img.buffer_flat(), 3),
+            OriginalImage::Rgba8(img) => split_channels(img.buffer_flat(), 4),
+        };
+        Self {
+            width,
+            height,
+            channels,
+        }
+    }
+
+    fn into_rgb(self, width: NonZeroU32, height: NonZeroU32) -> RgbImageInterleaved<u8> {
+        let to_u8 = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+        let pixels = match self.channels.as_slice() {
+            [gray] => gray.iter().map(|v| [to_u8(*v); 3]).collect(),
+            [r, g, b] => (0..r.len())
+                .map(|i| [to_u8(r[i]), to_u8(g[i]), to_u8(b[i])])
+                .collect(),
+            _ => unreachable!("Planes are either gray or rgb"),
+        };
+        Image::new_vec(pixels, width, height)
+    }
+}
+
+fn split_channels(interleaved: &[u8], stride: usize) -> Vec<Vec<f32>> {
+    (0..3)
+        .map(|c| {
+            interleaved
+                .chunks_exact(stride)
+                .map(|p| p[c] as f32 / 255.0)
+                .collect()
+        })
+        .collect()
+}
+
+/// Positions in the sorted values of a plane, which [`stretch`] maps to black and white
+fn stretch_ranks(len: usize) -> [usize; 2] {
+    let five_percent_pos = len / 20;
+    [five_percent_pos, five_percent_pos * 19]
+}
+
+/// Stretches the 5% to 95% percentile to the full range, like the contrast fix while loading
+fn stretch(mut plane: Vec<f32>) -> Vec<f32> {
+    let mut sorted = plane.clone();
+    sorted.sort_unstable_by(f32::total_cmp);
+    let [lower, upper] = stretch_ranks(sorted.len()).map(|rank| sorted[rank]);
+    if lower < upper {
+        let range = upper - lower;
+        plane
+            .iter_mut()
+            .for_each(|v| *v = ((*v - lower) / range).clamp(0.0, 1.0));
+    }
+    plane
+}
+
+fn clahe(plane: &mut [f32], width: usize, height: usize, tiles: usize, clip_limit: f32) {
+    const BINS: usize = 256;
+    let bin = |v: f32| (v.clamp(0.0, 1.0) * (BINS - 1) as f32).round() as usize;
+    let tile_w = width.div_ceil(tiles.clamp(1, width));
+    let tile_h = height.div_ceil(tiles.clamp(1, height));
+    let (tiles_x, tiles_y) = (width.div_ceil(tile_w), height.div_ceil(tile_h));
+
+    let luts = (0..tiles_y * tiles_x)
+        .map(|tile| {
+            let (tx, ty) = (tile % tiles_x, tile / tiles_x);
+            let mut histogram = [0u32; BINS];
+            let ys = ty * tile_h..((ty + 1) * tile_h).min(height);
+            let xs = tx * tile_w..((tx + 1) * tile_w).min(width);
+            for y in ys.clone() {
+                for v in &plane[y * width + xs.start..y * width + xs.end] {
+                    histogram[bin(*v)] += 1;
+                }
+            }
+            let count = (ys.len() * xs.len()) as u32;
+            let limit = ((clip_limit * count as f32 / BINS as f32) as u32).max(1);
+            let excess: u32 = histogram.iter().map(|h| h.saturating_sub(limit)).sum();
+            let (share, remainder) = (excess / BINS as u32, excess as usize % BINS);
+            let mut cumulative = 0;
+            std::array::from_fn::<f32, BINS, _>(|i| {
+                let clipped = histogram[i].min(limit) + share + u32::from(i < remainder);
+                cumulative += clipped;
+                cumulative as f32 / count as f32
+            })
+        })
+        .collect::<Vec<_>>();
+
+    // Bilinear interpolation between the mappings of the neighbouring tile centers
+    let neighbours = |pos: usize, size: usize, count: usize| {
+        let f = (pos as f32 + 0.5) / size as f32 - 0.5;
+        let low = (f.floor().max(0.0) as usize).min(count - 1);
+        let high = (low + 1).min(count - 1);
+        (low, high, (f - low as f32).clamp(0.0, 1.0))
+    };
+    for y in 0..height {
+        let (ty0, ty1, ay) = neighbours(y, tile_h, tiles_y);
+        for x in 0..width {
+            let (tx0, tx1, ax) = neighbours(x, tile_w, tiles_x);
+            let v = &mut plane[y * width + x];
+            let b = bin(*v);
+            let lut = |tx: usize, ty: usize| luts[ty * tiles_x + tx][b];
+            let top = lut(tx0, ty0) * (1.0 - ax) + lut(tx1, ty0) * ax;
+            let bottom = lut(tx0, ty1) * (1.0 - ax) + lut(tx1, ty1) * ax;
+            *v = top * (1.0 - ay) + bottom * ay;
+        }
+    }
+}
+
+fn box_blur(plane: &[f32], width: usize, height: usize, radius: usize) -> Vec<f32> {
+    fn blur_line(line: &[f32], radius: usize) -> Vec<f32> {
+        let mut prefix = Vec::with_capacity(line.len() + 1);
+        prefix.push(0.0f64);
+        for v in line {
+            prefix.push(prefix.last().unwrap() + *v as f64);
+        }
+        (0..line.len())
+            .map(|i| {
+                let (start, end) = (i.saturating_sub(radius), (i + radius + 1).min(line.len()));
+                ((prefix[end] - prefix[start]) / (end - start) as f64) as f32
+            })
+            .collect()
+    }
+
+    let mut horizontal = plane
+        .chunks_exact(width)
+        .flat_map(|row| blur_line(row, radius))
+        .collect::<Vec<_>>();
+    let mut column = vec![0.0; height];
+    for x in 0..width {
+        for (y, v) in column.iter_mut().enumerate() {
+            *v = horizontal[y * width + x];
+        }
+        for (y, v) in blur_line(&column, radius).into_iter().enumerate() {
+            horizontal[y * width + x] = v;
+        }
+    }
+    horizontal
+}
+
+fn median(plane: &[f32], width: usize, height: usize, radius: usize) -> Vec<f32> {
+    let mut window = Vec::with_capacity((2 * radius + 1).pow(2));
+    (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            window.clear();
+            for wy in y.saturating_sub(radius)..(y + radius + 1).min(height) {
+                let row = &plane[wy * width..(wy + 1) * width];
+                window
+                    .extend_from_slice(&row[x.saturating_sub(radius)..(x + radius + 1).min(width)]);
+            }
+            let mid = window.len() / 2;
+            *window.select_nth_unstable_by(mid, f32::total_cmp).1
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE_5: NonZeroU32 = NonZeroU32::new(5).unwrap();
+
+    fn gray(pixels: Vec<u8>) -> OriginalImage {
+        OriginalImage::Luma8(Image::new_vec(pixels, SIZE_5, SIZE_5))
+    }
+
+    fn red_channel(image: &RgbImageInterleaved<u8>) -> Vec<u8> {
+        image.buffer_flat().chunks_exact(3).map(|p| p[0]).collect()
+    }
+
+    #[test]
+    fn empty_chain_stretches_gray_images() {
+        let original = gray((0..25).map(|i| i * 10).collect());
+        let adjust = FilterChain::default().apply(&original);
+        let values = red_channel(&adjust);
+        assert_eq!(values[1], 0);
+        assert_eq!(values[23], 255);
+    }
+
+    #[test]
+    fn display_window_matches_stretch() {
+        let original = gray((0..25).map(|i| i * 10).collect());
+        let histogram = Histogram::new(&original, crate::HistogramChannel::Luminance);
+        let chain = FilterChain(vec![ImageFilter::Gamma { gamma: 2.0 }]);
+        assert_eq!(chain.display_window(&original, &histogram), Some(10..=190));
+        let values = red_channel(&chain.apply(&original));
+        assert_eq!((values[0], values[1], values[19]), (0, 0, 255));
+
+        let clahe = FilterChain(vec![ImageFilter::defaults()[1].clone()]);
+        assert_eq!(clahe.display_window(&original, &histogram), None);
+    }
+
+    #[test]
+    fn invert_and_gamma() {
+        let original = OriginalImage::Rgb8(Image::new_vec(vec![[0, 51, 255]; 25], SIZE_5, SIZE_5));
+        let chain = FilterChain(vec![ImageFilter::Gamma { gamma: 2.0 }, ImageFilter::Invert]);
+        let adjust = chain.apply(&original);
+        assert_eq!(&adjust.buffer_flat()[0..3], &[255, 245, 0]);
+    }
+
+    #[test]
+    fn median_removes_single_outlier() {
+        let mut pixels = vec![100; 25];
+        pixels[12] = 255;
+        let original = OriginalImage::Rgb8(Image::new_vec(
+            pixels.into_iter().map(|v| [v; 3]).collect(),
+            SIZE_5,
+            SIZE_5,
+        ));
+        let chain = FilterChain(vec![ImageFilter::MedianDenoise { radius: 1 }]);
+        assert_eq!(red_channel(&chain.apply(&original)), vec![100; 25]);
+    }
+
+    #[test]
+    fn unsharp_mask_keeps_flat_regions() {
+        let original = OriginalImage::Rgb8(Image::new_vec(vec![[80; 3]; 25], SIZE_5, SIZE_5));
+        let chain = FilterChain(vec![ImageFilter::UnsharpMask {
+            radius: 2,
+            amount: 3.0,
+        }]);
+        assert_eq!(red_channel(&chain.apply(&original)), vec![80; 25]);
+    }
+
+    #[test]
+    fn clahe_preserves_order() {
+        let original = OriginalImage::Rgb8(Image::new_vec(
+            (0..25).map(|i| [i * 4; 3]).collect(),
+            SIZE_5,
+            SIZE_5,
+        ));
+        let chain = FilterChain(vec![ImageFilter::Clahe {
+            tiles: 1,
+            clip_limit: 4.0,
+        }]);
+        let values = red_channel(&chain.apply(&original));
+        assert!(values.windows(2).all(|w| w[0] <= w[1]), "{values:?}");
+        assert_eq!(values[24], 255);
+    }
+}
@@ -0,0 +1,348 @@
+use std::ops::RangeInclusive;
+
+use egui::{Color32, ComboBox, Sense, Stroke, Vec2};
+
+use crate::{ImageId, ImageStateLoaded, OriginalImage, PixelArea};
+
+/// Channel evaluated by a [`Histogram`]. Grayscale images yield the same values for every channel.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HistogramChannel {
+    #[default]
+    Luminance,
+    Red,
+    Green,
+    Blue,
+}
+
+impl HistogramChannel {
+    pub const ALL: [Self; 4] = [Self::Luminance, Self::Red, Self::Green, Self::Blue];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HistogramChannel::Luminance => "Luminance",
+            HistogramChannel::Red => "Red",
+            HistogramChannel::Green => "Green",
+            HistogramChannel::Blue => "Blue",
+        }
+    }
+
+    fn rgb_value(self, [r, g, b]: [u8; 3]) -> u32 {
+        match self {
+            HistogramChannel::Luminance => {
+                (299 * r as u32 + 587 * g as u32 + 114 * b as u32 + 500) / 1000
+            }
+            HistogramChannel::Red => r as u32,
+            HistogramChannel::Green => g as u32,
+            HistogramChannel::Blue => b as u32,
+        }
+    }
+
+    /// Value of the pixel at `idx` (row-major) in the range `0..=image.max_value()`
+    pub fn value(self, image: &OriginalImage, idx: usize) -> u32 {
+        match image {
+            OriginalImage::Luma8(img) => img.buffer()[idx] as u32,
+            OriginalImage::Luma16(img) => img.buffer()[idx] as u32,
+            OriginalImage::Rgb8(img) => {
+                let p = &img.buffer_flat()[idx * 3..idx * 3 + 3];
+                self.rgb_value([p[0], p[1], p[2]])
+            }
+            OriginalImage::Rgba8(img) => {
+                let p = &img.buffer_flat()[idx * 4..idx * 4 + 3];
+                self.rgb_value([p[0], p[1], p[2]])
+            }
+        }
+    }
+}
+
+/// Summary of the intensities of a set of pixels
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PixelStatistics {
+    pub count: u64,
+    pub min: u32,
+    pub max: u32,
+    pub mean: f64,
+    pub std_dev: f64,
+}
+
+impl PixelStatistics {
+    pub fn from_values(values: impl IntoIterator<Item = u32>) -> Self {
+        Self::from_counts(values.into_iter().map(|v| (v, 1)))
+    }
+
+    /// Statistics of the pixels covered by `area`
+    pub fn from_area(image: &OriginalImage, channel: HistogramChannel, area: &PixelArea) -> Self {
+        Self::from_values(
+            area.global_ranges(image.width())
+                .flat_map(|(range, _)| range.map(|idx| channel.value(image, idx as usize))),
+        )
+    }
+
+    fn from_counts(counts: impl IntoIterator<Item = (u32, u64)>) -> Self {
+        let (mut count, mut sum, mut sum_sq) = (0u64, 0f64, 0f64);
+        let (mut min, mut max) = (u32::MAX, 0);
+        for (value, n) in counts.into_iter().filter(|(_, n)| *n > 0) {
+            count += n;
+            sum += value as f64 * n as f64;
+            sum_sq += (value as f64).powi(2) * n as f64;
+            min = min.min(value);
+            max = max.max(value);
+        }
+        if count == 0 {
+            return Self::default();
+        }
+        let mean = sum / count as f64;
+        Self {
+            count,
+            min,
+            max,
+            mean,
+            std_dev: (sum_sq / count as f64 - mean * mean).max(0.0).sqrt(),
+        }
+    }
+
+    pub fn ui(&self, ui: &mut egui::Ui) {
+        ui.label(format!(
+            "n: {}, min: {}, max: {}\nmean: {:.2}, std: {:.2}",
+            self.count, self.min, self.max, self.mean, self.std_dev
+        ));
+    }
+}
+
+/// Intensity distribution of an [`OriginalImage`] with one bin per possible value.
+/// Luma16 images therefore use the full 16-bit range.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram {
+    bins: Vec<u64>,
+    statistics: PixelStatistics,
+}
+
+impl Histogram {
+    pub fn new(image: &OriginalImage, channel: HistogramChannel) -> Self {
+        let mut bins = vec![0u64; image.max_value() as usize + 1];
+        let pixel_count = image.width().get() as usize * image.height().get() as usize;
+        for idx in 0..pixel_count {
+            bins[channel.value(image, idx) as usize] += 1;
+        }
+        let statistics =
+            PixelStatistics::from_counts(bins.iter().enumerate().map(|(v, n)| (v as u32, *n)));
+        Self { bins, statistics }
+    }
+
+    pub fn bins(&self) -> &[u64] {
+        &self.bins
+    }
+
+    pub fn max_value(&self) -> u32 {
+        (self.bins.len() - 1) as u32
+    }
+
+    pub fn statistics(&self) -> &PixelStatistics {
+        &self.statistics
+    }
+
+    /// Smallest value, for which at least `fraction` (0..=1) of all pixels are smaller or equal
+    pub fn percentile(&self, fraction: f32) -> u32 {
+        let target = (self.statistics.count as f64 * fraction.clamp(0.0, 1.0) as f64).ceil() as u64;
+        self.value_at_rank(target.max(1) - 1)
+    }
+
+    /// Value at position `rank` if all pixel values were sorted
+    pub fn value_at_rank(&self, rank: u64) -> u32 {
+        let mut cumulative = 0;
+        for (value, n) in self.bins.iter().enumerate() {
+            cumulative += n;
+            if cumulative > rank {
+                return value as u32;
+            }
+        }
+        self.max_value()
+    }
+}
+
+/// Draws a [`Histogram`] with optional markers for the current contrast window
+pub struct HistogramWidget<'a> {
+    histogram: &'a Histogram,
+    window: Option<RangeInclusive<u32>>,
+    height: f32,
+}
+
+impl<'a> HistogramWidget<'a> {
+    pub fn new(histogram: &'a Histogram) -> Self {
+        Self {
+            histogram,
+            window: None,
+            height: 80.0,
+        }
+    }
+
+    pub fn window(mut self, window: RangeInclusive<u32>) -> Self {
+        self.window = Some(window);
+        self
+    }
+
+    pub fn height(mut self, height: f32) -> Self {
+        self.height = height;
+        self
+    }
+}
+
+impl egui::Widget for HistogramWidget<'_> {
+    fn ui(self, ui: &mut egui::Ui) -> egui::Response {
+        let (rect, response) =
+            ui.allocate_exact_size(Vec2::new(ui.available_width(), self.height), Sense::hover());
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 0.0, ui.visuals().extreme_bg_color);
+
+        // Multiple bins share a column, as 16-bit histograms are much wider than the widget
+        let bins = self.histogram.bins();
+        let columns = (rect.width() as usize).max(1);
+        let column_counts = (0..columns)
+            .map(|c| {
+                let start = (c * bins.len() / columns).min(bins.len() - 1);
+                let end = ((c + 1) * bins.len() / columns).clamp(start + 1, bins.len());
+                bins[start..end].iter().sum::<u64>()
+            })
+            .collect::<Vec<_>>();
+        let max_count = column_counts.iter().copied().max().unwrap_or(0).max(1);
+        let bar_stroke = Stroke::new(1.0, ui.visuals().text_color());
+        for (c, count) in column_counts.into_iter().enumerate() {
+            if count > 0 {
+                let bar_height = rect.height() * count as f32 / max_count as f32;
+                let x = rect.left() + c as f32 + 0.5;
+                painter.vline(x, (rect.bottom() - bar_height)..=rect.bottom(), bar_stroke);
+            }
+        }
+
+        if let Some(window) = self.window {
+            let max_value = self.histogram.max_value().max(1) as f32;
+            for value in [*window.start(), *window.end()] {
+                let x = rect.left() + rect.width() * value as f32 / max_value;
+                painter.vline(x, rect.y_range(), Stroke::new(1.0, Color32::RED));
+            }
+        }
+        response.on_hover_text(format!("0..={}", self.histogram.max_value()))
+    }
+}
+
+/// Histogram and statistics of the loaded image. Statistics are additionally shown for the
+/// selected mask.
+#[derive(Default)]
+pub struct HistogramPanel {
+    channel: HistogramChannel,
+    cache: Option<(ImageId, HistogramChannel, Histogram)>,
+    /// Statistics of the selected mask with the mask revision and layer they were computed for
+    mask_cache: Option<(ImageId, HistogramChannel, u64, usize, PixelStatistics)>,
+}
+
+impl HistogramPanel {
+    pub fn ui(&mut self, ui: &mut egui::Ui, image: &ImageStateLoaded) {
+        let original = &image.image.original;
+        ComboBox::from_id_salt("histogram_channel")
+            .selected_text(self.channel.name())
+            .show_ui(ui, |ui| {
+                for channel in HistogramChannel::ALL {
+                    ui.selectable_value(&mut self.channel, channel, channel.name());
+                }
+            });
+
+        if !matches!(&self.cache, Some((id, channel, _)) if *id == image.id && *channel == self.channel)
+        {
+            self.cache = Some((
+                image.id.clone(),
+                self.channel,
+                Histogram::new(original, self.channel),
+            ));
+        }
+        let Some((_, _, histogram)) = &self.cache else {
+            unreachable!("Cache is filled above")
+        };
+
+        let mut widget = HistogramWidget::new(histogram);
+        // The histogram is of the primary image, so the window of an auxiliary base is not shown
+        let window = image
+            .base()
+            .is_none()
+            .then(|| image.applied_filters().display_window(original, histogram))
+            .flatten();
+        if let Some(window) = window {
+            widget = widget.window(window);
+        }
+        ui.add(widget);
+        histogram.statistics().ui(ui);
+
+        let Some(layer) = image.masks.selected() else {
+            return;
+        };
+        let revision = image.masks.revision();
+        if !matches!(
+            &self.mask_cache,
+            Some((id, channel, cached_revision, cached_layer, _))
+                if *id == image.id
+                    && *channel == self.channel
+                    && *cached_revision == revision
+                    && *cached_layer == layer
+        ) && let Some(Some(mask)) = image.masks.areas().get(layer)
+        {
+            let statistics = PixelStatistics::from_area(original, self.channel, mask);
+            self.mask_cache = Some((image.id.clone(), self.channel, revision, layer, statistics));
+        }
+        if let Some((_, _, _, _, statistics)) = &self.mask_cache {
+            ui.label("Selected mask:");
+            statistics.ui(ui);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU32;
+
+    use imbuf::Image;
+
+    use super::*;
+
+    const WIDTH_4: NonZeroU32 = NonZeroU32::new(4).unwrap();
+    const HEIGHT_2: NonZeroU32 = NonZeroU32::new(2).unwrap();
+
+    fn luma16_image() -> OriginalImage {
+        OriginalImage::Luma16(Image::new_vec(
+            vec![0, 1000, 1000, 65535, 7, 7, 7, 7],
+            WIDTH_4,
+            HEIGHT_2,
+        ))
+    }
+
+    #[test]
+    fn luma16_histogram_uses_full_range() {
+        let histogram = Histogram::new(&luma16_image(), HistogramChannel::Luminance);
+        assert_eq!(histogram.bins().len(), 65536);
+        assert_eq!(histogram.bins()[1000], 2);
+        assert_eq!(histogram.bins()[7], 4);
+        assert_eq!(histogram.statistics().count, 8);
+        assert_eq!(histogram.statistics().min, 0);
+        assert_eq!(histogram.statistics().max, 65535);
+        assert_eq!(histogram.percentile(0.5), 7);
+        assert_eq!(histogram.percentile(1.0), 65535);
+    }
+
+    #[test]
+    fn rgb_histogram_per_channel() {
+        let image = OriginalImage::Rgb8(Image::new_vec(vec![[10, 20, 30]; 8], WIDTH_4, HEIGHT_2));
+        let red = Histogram::new(&image, HistogramChannel::Red);
+        let blue = Histogram::new(&image, HistogramChannel::Blue);
+        assert_eq!(red.bins().len(), 256);
+        assert_eq!(red.bins()[10], 8);
+        assert_eq!(blue.bins()[30], 8);
+    }
+
+    #[test]
+    fn statistics_of_area() {
+        let area = PixelArea::single_range_total_black(1, 0, NonZeroU32::new(2).unwrap(), WIDTH_4);
+        let stats = PixelStatistics::from_area(&luma16_image(), HistogramChannel::Luminance, &area);
+        assert_eq!(stats.count, 2);
+        assert_eq!(stats.min, 1000);
+        assert_eq!(stats.max, 1000);
+        assert_eq!(stats.mean, 1000.0);
+        assert_eq!(stats.std_dev, 0.0);
+    }
+}
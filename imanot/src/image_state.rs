@@ -1,11 +1,12 @@
-use std::{io, num::NonZeroU32};
+use std::{io, num::NonZeroU32, sync::Arc};
 
-use egui::{
-    self, Color32, ColorImage, ImageSource, TextureHandle, TextureOptions, load::SizedTexture,
-};
+use egui::{self, ColorImage, ImageSource, TextureHandle, TextureOptions, load::SizedTexture};
 use futures::FutureExt;
 
-use crate::{AsyncTask, ImageData, ImageId, ImageLoadOk, MaskImage};
+use crate::{
+    AsyncTask, ClassId, DisplayMode, FilterChain, FilterTask, ImageData, ImageId, ImageLoadOk,
+    LabelSchema, MaskImage, MaskSettings, ViewerLayer,
+};
 
 #[allow(clippy::large_enum_variant)]
 pub enum ImageState {
@@ -16,16 +17,44 @@ pub enum ImageState {
 }
 
 impl ImageState {
-    pub fn sources(
-        &mut self,
-        ctx: &egui::Context,
-    ) -> impl Iterator<Item = ImageSource<'static>> + '_ {
+    pub fn sources(&mut self, ctx: &egui::Context) -> impl Iterator<Item = ViewerLayer> + '_ {
         match self {
             ImageState::Loaded(x) => itertools::Either::Left(x.sources(ctx)),
             _ => itertools::Either::Right(std::iter::empty()),
         }
     }
 
+    /// Rerenders the image texture of a loaded image. Masks are not affected.
+    pub fn set_display_mode(&mut self, mode: DisplayMode) {
+        if let ImageState::Loaded(x) = self {
+            x.set_display_mode(mode);
+        }
+    }
+
+    /// Recomputes `adjust` of a loaded image in the background, if the filters changed
+    pub fn set_filters(&mut self, filters: &FilterChain) {
+        if let ImageState::Loaded(x) = self {
+            x.set_filters(filters);
+        }
+    }
+
+    /// Label schema and class of new masks of a loaded image
+    pub fn set_labels(&mut self, labels: &Arc<LabelSchema>, active_class: Option<ClassId>) {
+        if let ImageState::Loaded(x) = self {
+            x.masks.set_labels(labels);
+            x.masks.set_active_class(active_class);
+        }
+    }
+
+    /// Overlay settings of the masks of a loaded image
+    pub fn set_mask_settings(&mut self, settings: &MaskSettings) {
+        if let ImageState::Loaded(x) = self
+            && x.masks.settings() != settings
+        {
+            x.masks.set_settings(settings.clone());
+        }
+    }
+
     pub fn set_image_data(&mut self, image_data: ImageData) {
         *self = Self::LoadingImageData(AsyncTask::new(
             async move { std::io::Result::Ok(image_data) }.boxed(),
@@ -51,8 +80,9 @@ impl ImageState {
                     }
                 }
             }
-            ImageState::Loaded(ImageStateLoaded { masks, .. }) => {
-                masks.handle_events(ctx);
+            ImageState::Loaded(loaded) => {
+                loaded.masks.handle_events(ctx);
+                loaded.update_filters(ctx);
             }
             ImageState::Error(_error) => {}
         }
@@ -66,34 +96,183 @@ impl ImageStateLoaded {
         if width.get() as usize > max_texture_side || height.get() as usize > max_texture_side {
             return Err(TextureExceedsLimit::new(width, height, max_texture_side));
         }
-        let handle = ctx.load_texture(
-            "Overlays",
-            ColorImage::new(
-                [width.get() as _, height.get() as _],
-                i.image
-                    .adjust_pixels()
-                    .map(|(_, _, [r, g, b])| Color32::from_rgb(r, g, b))
-                    .collect(),
-            ),
-            TextureOptions {
-                magnification: egui::TextureFilter::Nearest,
-                ..Default::default()
-            },
-        );
-        let texture = SizedTexture::from_handle(&handle);
-
-        let source = ImageSource::Texture(texture);
+        let display_mode = DisplayMode::default();
+        let auxiliary = i
+            .auxiliary
+            .into_iter()
+            .filter_map(|aux| {
+                if aux.image.adjust.dimensions() != (width, height) {
+                    log::warn!(
+                        "Skip auxiliary image '{}': Dimensions {:?} don't match the primary image",
+                        aux.name,
+                        aux.image.adjust.dimensions()
+                    );
+                    return None;
+                }
+                Some(AuxiliaryLayer {
+                    texture: Self::load_texture(ctx, &aux.name, &aux.image, display_mode),
+                    name: aux.name,
+                    image: aux.image,
+                    opacity: 0.0,
+                })
+            })
+            .collect();
+
         Ok(ImageStateLoaded {
+            texture: Self::load_texture(ctx, "Image", &i.image, display_mode),
             id: i.id,
             image: i.image,
-            texture: (handle, source),
             masks: MaskImage::new(
                 [width.get() as usize, height.get() as usize],
                 i.masks.clone(),
                 Default::default(),
             ),
+            auxiliary,
+            base: None,
+            display_mode,
+            filters: FilterChain::default(),
+            applied_filters: FilterChain::default(),
+            filter_task: None,
+            failed_filters: None,
         })
     }
+
+    fn load_texture(
+        ctx: &egui::Context,
+        name: &str,
+        image: &ImageLoadOk,
+        mode: DisplayMode,
+    ) -> (TextureHandle, ImageSource<'static>) {
+        let handle = ctx.load_texture(name, Self::render(image, mode), Self::TEXTURE_OPTIONS);
+        let source = ImageSource::Texture(SizedTexture::from_handle(&handle));
+        (handle, source)
+    }
+
+    const TEXTURE_OPTIONS: TextureOptions = TextureOptions {
+        magnification: egui::TextureFilter::Nearest,
+        ..TextureOptions::LINEAR
+    };
+
+    fn render(image: &ImageLoadOk, mode: DisplayMode) -> ColorImage {
+        let (width, height) = image.adjust.dimensions();
+        let mapper = mode.mapper();
+        ColorImage::new(
+            [width.get() as _, height.get() as _],
+            image
+                .adjust_pixels()
+                .map(|(_, _, rgb)| mapper(rgb))
+                .collect(),
+        )
+    }
+
+    pub fn display_mode(&self) -> DisplayMode {
+        self.display_mode
+    }
+
+    /// Updates the image textures in place, the texture of the masks is kept
+    pub fn set_display_mode(&mut self, mode: DisplayMode) {
+        if mode != self.display_mode {
+            self.display_mode = mode;
+            self.texture
+                .0
+                .set(Self::render(&self.image, mode), Self::TEXTURE_OPTIONS);
+            for aux in &mut self.auxiliary {
+                aux.texture
+                    .0
+                    .set(Self::render(&aux.image, mode), Self::TEXTURE_OPTIONS);
+            }
+        }
+    }
+
+    pub fn filters(&self) -> &FilterChain {
+        &self.filters
+    }
+
+    /// Filters `image.adjust` was computed with, which lag behind [`Self::filters`] while
+    /// a computation is running
+    pub fn applied_filters(&self) -> &FilterChain {
+        &self.applied_filters
+    }
+
+    /// Requests a recomputation of `image.adjust`. Only the last requested chain is computed,
+    /// if the chain changes while a computation is running.
+    pub fn set_filters(&mut self, filters: &FilterChain) {
+        if *filters != self.filters {
+            self.filters = filters.clone();
+        }
+    }
+
+    fn update_filters(&mut self, ctx: &egui::Context) {
+        if let Some((chain, task)) = &mut self.filter_task {
+            let Some(result) = task.data() else {
+                ctx.request_repaint();
+                return;
+            };
+            let chain = chain.clone();
+            self.filter_task = None;
+            match result {
+                Ok(adjust) => {
+                    self.applied_filters = chain;
+                    self.image.adjust = adjust;
+                    self.texture.0.set(
+                        Self::render(&self.image, self.display_mode),
+                        Self::TEXTURE_OPTIONS,
+                    );
+                }
+                Err(e) => {
+                    log::error!("Failed to apply filters {chain:?}: {e}");
+                    self.failed_filters = Some(chain);
+                }
+            }
+        }
+        // A failed chain isn't retried, the image stays as it was until the filters change
+        if self.filters != self.applied_filters
+            && self.failed_filters.as_ref() != Some(&self.filters)
+        {
+            let task = self.filters.apply_async(self.image.original.clone());
+            self.filter_task = Some((self.filters.clone(), task));
+            ctx.request_repaint();
+        }
+    }
+
+    /// Index into `auxiliary` of the image shown instead of the primary image
+    pub fn base(&self) -> Option<usize> {
+        self.base
+    }
+
+    /// Shows the auxiliary image at `idx` instead of the primary image. `None` restores the primary image.
+    pub fn set_base(&mut self, base: Option<usize>) {
+        self.base = base.filter(|idx| *idx < self.auxiliary.len());
+    }
+
+    /// Image which is currently displayed as base layer
+    pub fn base_image(&self) -> &ImageLoadOk {
+        match self.base {
+            Some(idx) => &self.auxiliary[idx].image,
+            None => &self.image,
+        }
+    }
+
+    /// Primary image for `None`, otherwise the auxiliary image with the given name
+    pub fn image_by_name(&self, name: Option<&str>) -> Option<&ImageLoadOk> {
+        match name {
+            None => Some(&self.image),
+            Some(name) => self
+                .auxiliary
+                .iter()
+                .find(|aux| aux.name == name)
+                .map(|aux| &aux.image),
+        }
+    }
+}
+
+/// Auxiliary image of a loaded image, which can be blended over the base layer or used as base itself
+pub struct AuxiliaryLayer {
+    pub name: String,
+    pub image: ImageLoadOk,
+    /// Opacity when drawn over the base layer. The layer is hidden for 0.0
+    pub opacity: f32,
+    texture: (TextureHandle, ImageSource<'static>),
 }
 
 pub struct ImageStateLoaded {
@@ -105,14 +284,36 @@ pub struct ImageStateLoaded {
     pub texture: (TextureHandle, ImageSource<'static>),
     pub image: ImageLoadOk,
     pub masks: MaskImage,
+    pub auxiliary: Vec<AuxiliaryLayer>,
+    base: Option<usize>,
+    display_mode: DisplayMode,
+    /// Filters requested for `image.adjust`
+    filters: FilterChain,
+    /// Filters `image.adjust` was computed with
+    applied_filters: FilterChain,
+    filter_task: Option<(FilterChain, FilterTask)>,
+    /// Last chain whose computation failed
+    failed_filters: Option<FilterChain>,
 }
 
 impl ImageStateLoaded {
-    pub fn sources(
-        &mut self,
-        ctx: &egui::Context,
-    ) -> impl Iterator<Item = ImageSource<'static>> + '_ {
-        std::iter::once(self.texture.1.clone()).chain(self.masks.sources(ctx))
+    pub fn sources(&mut self, ctx: &egui::Context) -> impl Iterator<Item = ViewerLayer> + '_ {
+        let base_source = match self.base {
+            Some(idx) => &self.auxiliary[idx].texture.1,
+            None => &self.texture.1,
+        };
+        let images = std::iter::once(ViewerLayer::from(base_source.clone()))
+            .chain(
+                self.auxiliary
+                    .iter()
+                    .enumerate()
+                    .filter(|(idx, aux)| Some(*idx) != self.base && aux.opacity > 0.0)
+                    .map(|(_, aux)| ViewerLayer::with_opacity(aux.texture.1.clone(), aux.opacity)),
+            )
+            .collect::<Vec<_>>();
+        images
+            .into_iter()
+            .chain(self.masks.sources(ctx).map(ViewerLayer::from))
     }
 }
 
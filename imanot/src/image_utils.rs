@@ -2,6 +2,8 @@ use std::num::NonZeroU32;
 
 use imbuf::Image;
 
+use crate::PixelArea;
+
 #[cfg(feature = "image-0_25")]
 mod image;
 
@@ -32,6 +34,92 @@ impl OriginalImage {
             OriginalImage::Rgba8(img) => img.dimensions().1,
         }
     }
+
+    /// Largest possible value of a single channel
+    pub fn max_value(&self) -> u32 {
+        match self {
+            OriginalImage::Luma16(_) => u16::MAX as u32,
+            _ => u8::MAX as u32,
+        }
+    }
+}
+
+/// Transformation which was applied to the raw image data during loading (e.g. EXIF orientation).
+/// `OriginalImage`, `adjust` and all masks are in the transformed orientation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ImageOrientation {
+    #[default]
+    NoTransforms,
+    /// Rotated by 90 degrees clockwise
+    Rotate90,
+    Rotate180,
+    /// Rotated by 270 degrees clockwise
+    Rotate270,
+    FlipHorizontal,
+    FlipVertical,
+    /// Rotated by 90 degrees clockwise, then flipped horizontally
+    Rotate90FlipH,
+    /// Rotated by 270 degrees clockwise, then flipped horizontally
+    Rotate270FlipH,
+}
+
+impl ImageOrientation {
+    /// True if width and height of the raw image are swapped
+    pub fn swaps_axes(self) -> bool {
+        matches!(
+            self,
+            Self::Rotate90 | Self::Rotate270 | Self::Rotate90FlipH | Self::Rotate270FlipH
+        )
+    }
+
+    /// Dimensions of the raw image, given the dimensions of the transformed image
+    pub fn raw_dimensions(self, width: NonZeroU32, height: NonZeroU32) -> (NonZeroU32, NonZeroU32) {
+        if self.swaps_axes() {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+
+    /// Maps the pixel (x, y) of the transformed image with the given dimensions to the raw image
+    pub fn raw_position(self, x: u32, y: u32, width: NonZeroU32, height: NonZeroU32) -> (u32, u32) {
+        let (max_x, max_y) = (width.get() - 1, height.get() - 1);
+        match self {
+            Self::NoTransforms => (x, y),
+            Self::Rotate90 => (y, max_x - x),
+            Self::Rotate180 => (max_x - x, max_y - y),
+            Self::Rotate270 => (max_y - y, x),
+            Self::FlipHorizontal => (max_x - x, y),
+            Self::FlipVertical => (x, max_y - y),
+            Self::Rotate90FlipH => (y, x),
+            Self::Rotate270FlipH => (max_y - y, max_x - x),
+        }
+    }
+
+    /// Maps a mask of the transformed image with the given dimensions to the raw image.
+    /// Every row run stays a run of the raw image or becomes a column of single pixels.
+    pub fn raw_pixel_area(
+        self,
+        area: &PixelArea,
+        width: NonZeroU32,
+        height: NonZeroU32,
+    ) -> Option<PixelArea> {
+        let mut runs = Vec::new();
+        for (y, x_range, meta) in area.row_runs() {
+            let raw = |x: u64| self.raw_position(x as u32, y as u32, width, height);
+            let (first, last) = (raw(x_range.start), raw(x_range.end - 1));
+            if first.1 == last.1 {
+                let (start, end) = (first.0.min(last.0), first.0.max(last.0));
+                runs.push((first.1 as u64, start as u64..end as u64 + 1, meta));
+            } else {
+                for raw_y in first.1.min(last.1)..=first.1.max(last.1) {
+                    runs.push((raw_y as u64, first.0 as u64..first.0 as u64 + 1, meta));
+                }
+            }
+        }
+        runs.sort_unstable_by_key(|(y, x_range, _)| (*y, x_range.start));
+        PixelArea::from_row_runs(runs, area.color).map(|raw| raw.with_properties_of(area))
+    }
 }
 
 /// Represents a loaded image using image-buffer
@@ -39,6 +127,8 @@ impl OriginalImage {
 pub struct ImageLoadOk {
     pub original: OriginalImage,
     pub adjust: Image<[u8; 3], 1>,
+    /// Transformation applied to the raw image data during loading
+    pub orientation: ImageOrientation,
 }
 
 impl ImageLoadOk {
@@ -60,3 +150,52 @@ impl ImageLoadOk {
 
 #[cfg(feature = "image-0_25")]
 pub use image::load_image;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORIENTATIONS: [ImageOrientation; 8] = [
+        ImageOrientation::NoTransforms,
+        ImageOrientation::Rotate90,
+        ImageOrientation::Rotate180,
+        ImageOrientation::Rotate270,
+        ImageOrientation::FlipHorizontal,
+        ImageOrientation::FlipVertical,
+        ImageOrientation::Rotate90FlipH,
+        ImageOrientation::Rotate270FlipH,
+    ];
+
+    #[test]
+    fn raw_pixel_area_matches_raw_position() {
+        // 4x3 image with an L shape: (1..4, 0), (1, 1) and (1..3, 2)
+        let (width, height) = (NonZeroU32::new(4).unwrap(), NonZeroU32::new(3).unwrap());
+        let runs = [(0, 1..4), (1, 1..2), (2, 1..3)];
+        let area = PixelArea::from_row_runs(
+            runs.iter()
+                .map(|(y, x_range)| (*y, x_range.clone(), crate::Meta::default())),
+            [1, 2, 3],
+        )
+        .unwrap();
+        for orientation in ORIENTATIONS {
+            let mut expected = runs
+                .iter()
+                .flat_map(|(y, x_range)| {
+                    x_range.clone().map(move |x| {
+                        let (raw_x, raw_y) =
+                            orientation.raw_position(x as u32, *y as u32, width, height);
+                        (raw_y, raw_x)
+                    })
+                })
+                .collect::<Vec<_>>();
+            expected.sort_unstable();
+            let raw = orientation.raw_pixel_area(&area, width, height).unwrap();
+            let actual = raw
+                .row_runs()
+                .flat_map(|(y, x_range, _)| x_range.map(move |x| (y as u32, x as u32)))
+                .collect::<Vec<_>>();
+            assert_eq!(actual, expected, "{orientation:?}");
+            assert_eq!(raw.color, [1, 2, 3]);
+        }
+    }
+}
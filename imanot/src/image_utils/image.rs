@@ -1,9 +1,11 @@
 use std::num::NonZeroU32;
 
 use image_0_25 as image;
-use image_0_25::{DynamicImage, ImageBuffer as ImageImageBuffer, Luma};
+use image_0_25::{
+    DynamicImage, ImageBuffer as ImageImageBuffer, ImageDecoder, Luma, metadata::Orientation,
+};
 
-use crate::image_utils::{ImageLoadOk, OriginalImage};
+use crate::image_utils::{ImageLoadOk, ImageOrientation, OriginalImage};
 use imbuf::Image;
 
 impl OriginalImage {
@@ -52,9 +54,34 @@ impl ImageLoadOk {
     }
 }
 
+impl From<Orientation> for ImageOrientation {
+    fn from(value: Orientation) -> Self {
+        match value {
+            Orientation::NoTransforms => Self::NoTransforms,
+            Orientation::Rotate90 => Self::Rotate90,
+            Orientation::Rotate180 => Self::Rotate180,
+            Orientation::Rotate270 => Self::Rotate270,
+            Orientation::FlipHorizontal => Self::FlipHorizontal,
+            Orientation::FlipVertical => Self::FlipVertical,
+            Orientation::Rotate90FlipH => Self::Rotate90FlipH,
+            Orientation::Rotate270FlipH => Self::Rotate270FlipH,
+        }
+    }
+}
+
+/// Decodes the image and applies its EXIF orientation, so the result is displayed upright
 pub fn load_image(bytes: &[u8]) -> std::io::Result<ImageLoadOk> {
-    let original = image::load_from_memory(bytes)
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    fn invalid_data(e: image::ImageError) -> std::io::Error {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, e)
+    }
+    let mut decoder = image::ImageReader::new(std::io::Cursor::new(bytes))
+        .with_guessed_format()?
+        .into_decoder()
+        .map_err(invalid_data)?;
+    let exif_orientation = decoder.orientation().map_err(invalid_data)?;
+    let mut original = DynamicImage::from_decoder(decoder).map_err(invalid_data)?;
+    original.apply_orientation(exif_orientation);
+    let orientation = ImageOrientation::from(exif_orientation);
 
     Ok(match &original {
         DynamicImage::ImageLuma16(i) => {
@@ -64,6 +91,7 @@ pub fn load_image(bytes: &[u8]) -> std::io::Result<ImageLoadOk> {
             ImageLoadOk {
                 original: OriginalImage::Luma16(original_buffer),
                 adjust: adjust_buffer,
+                orientation,
             }
         }
         DynamicImage::ImageLuma8(i) => {
@@ -73,6 +101,7 @@ pub fn load_image(bytes: &[u8]) -> std::io::Result<ImageLoadOk> {
             ImageLoadOk {
                 original: OriginalImage::Luma8(original_buffer),
                 adjust: adjust_buffer,
+                orientation,
             }
         }
         DynamicImage::ImageRgb8(i) => {
@@ -81,6 +110,7 @@ pub fn load_image(bytes: &[u8]) -> std::io::Result<ImageLoadOk> {
             ImageLoadOk {
                 original: OriginalImage::Rgb8(original_buffer),
                 adjust: adjust_buffer,
+                orientation,
             }
         }
         _ => {
@@ -88,6 +118,7 @@ pub fn load_image(bytes: &[u8]) -> std::io::Result<ImageLoadOk> {
             ImageLoadOk {
                 original: OriginalImage::Rgb8(original_buffer.clone()),
                 adjust: original_buffer,
+                orientation,
             }
         }
     })
@@ -200,6 +231,35 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn raw_position_matches_apply_orientation() {
+        let raw = DynamicImage::ImageLuma16(ImageImageBuffer::from_fn(3, 2, |x, y| {
+            Luma([(y * 3 + x) as u16])
+        }));
+        for exif in 1..=8 {
+            let exif_orientation = Orientation::from_exif(exif).unwrap();
+            let mut transformed = raw.clone();
+            transformed.apply_orientation(exif_orientation);
+            let transformed = transformed.into_luma16();
+            let width = NonZeroU32::new(transformed.width()).unwrap();
+            let height = NonZeroU32::new(transformed.height()).unwrap();
+            let orientation = ImageOrientation::from(exif_orientation);
+            assert_eq!(
+                orientation.raw_dimensions(width, height),
+                (NonZeroU32::new(3).unwrap(), NonZeroU32::new(2).unwrap())
+            );
+
+            for (x, y, Luma([value])) in transformed.enumerate_pixels() {
+                let (raw_x, raw_y) = orientation.raw_position(x, y, width, height);
+                assert_eq!(
+                    (raw_y * 3 + raw_x) as u16,
+                    *value,
+                    "{exif_orientation:?} at ({x}, {y})"
+                );
+            }
+        }
+    }
+
     #[test]
     fn fix_image_contrast_all_pixels_same() {
         let image = ImageImageBuffer::from_raw(5, 5, vec![255.into(); 25]).unwrap();
@@ -0,0 +1,259 @@
+use std::collections::HashSet;
+
+use egui::ComboBox;
+
+/// Identifier of a [`LabelClass`]. Stable across sessions, as it is stored with the masks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct ClassId(pub u16);
+
+impl ClassId {
+    /// Marks areas without class in stored masks, so no class may use it
+    pub const RESERVED: ClassId = ClassId(u16::MAX);
+}
+
+/// A class of the label taxonomy, e.g. "car" with parent "vehicle"
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct LabelClass {
+    pub id: ClassId,
+    pub name: String,
+    pub color: [u8; 3],
+    /// Name of an [`egui::Key`], e.g. "1" or "C", selecting this class
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub hotkey: Option<String>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub parent: Option<ClassId>,
+}
+
+impl LabelClass {
+    pub fn key(&self) -> Option<egui::Key> {
+        egui::Key::from_name(self.hotkey.as_deref()?)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum LabelSchemaError {
+    #[error("Class id {0:?} is used more than once")]
+    DuplicateId(ClassId),
+    #[error("Class id {0:?} is reserved for masks without class")]
+    ReservedId(ClassId),
+    #[error("Parent {parent:?} of class {class:?} does not exist")]
+    UnknownParent { class: ClassId, parent: ClassId },
+    #[error("Class {0:?} is its own ancestor")]
+    Cycle(ClassId),
+    #[error("Unknown hotkey '{hotkey}' of class {class:?}")]
+    UnknownHotkey { class: ClassId, hotkey: String },
+}
+
+/// Validated set of label classes. Parents always exist and the hierarchy is free of cycles.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(try_from = "Vec<LabelClass>", into = "Vec<LabelClass>")
+)]
+pub struct LabelSchema {
+    classes: Vec<LabelClass>,
+}
+
+impl TryFrom<Vec<LabelClass>> for LabelSchema {
+    type Error = LabelSchemaError;
+
+    fn try_from(classes: Vec<LabelClass>) -> Result<Self, Self::Error> {
+        Self::new(classes)
+    }
+}
+
+impl From<LabelSchema> for Vec<LabelClass> {
+    fn from(schema: LabelSchema) -> Self {
+        schema.classes
+    }
+}
+
+impl LabelSchema {
+    pub fn new(classes: Vec<LabelClass>) -> Result<Self, LabelSchemaError> {
+        let mut ids = HashSet::new();
+        for class in &classes {
+            if class.id == ClassId::RESERVED {
+                return Err(LabelSchemaError::ReservedId(class.id));
+            }
+            if !ids.insert(class.id) {
+                return Err(LabelSchemaError::DuplicateId(class.id));
+            }
+            if let Some(hotkey) = &class.hotkey
+                && class.key().is_none()
+            {
+                return Err(LabelSchemaError::UnknownHotkey {
+                    class: class.id,
+                    hotkey: hotkey.clone(),
+                });
+            }
+        }
+        let schema = Self { classes };
+        for class in &schema.classes {
+            let mut visited = HashSet::from([class.id]);
+            let mut current = class;
+            while let Some(parent) = current.parent {
+                current = schema.get(parent).ok_or(LabelSchemaError::UnknownParent {
+                    class: current.id,
+                    parent,
+                })?;
+                if !visited.insert(current.id) {
+                    return Err(LabelSchemaError::Cycle(class.id));
+                }
+            }
+        }
+        Ok(schema)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.classes.is_empty()
+    }
+
+    pub fn classes(&self) -> &[LabelClass] {
+        &self.classes
+    }
+
+    pub fn get(&self, id: ClassId) -> Option<&LabelClass> {
+        self.classes.iter().find(|c| c.id == id)
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<&LabelClass> {
+        self.classes.iter().find(|c| c.name == name)
+    }
+
+    pub fn color(&self, id: ClassId) -> Option<[u8; 3]> {
+        self.get(id).map(|c| c.color)
+    }
+
+    /// The class itself followed by its parent, grandparent, ...
+    pub fn ancestors(&self, id: ClassId) -> impl Iterator<Item = &LabelClass> {
+        std::iter::successors(self.get(id), |c| self.get(c.parent?))
+    }
+
+    /// Whether `id` is `ancestor` or one of its descendants
+    pub fn is_a(&self, id: ClassId, ancestor: ClassId) -> bool {
+        self.ancestors(id).any(|c| c.id == ancestor)
+    }
+
+    /// Name including all parents, e.g. "vehicle / car"
+    pub fn path(&self, id: ClassId) -> Option<String> {
+        let mut names = self
+            .ancestors(id)
+            .map(|c| c.name.as_str())
+            .collect::<Vec<_>>();
+        if names.is_empty() {
+            return None;
+        }
+        names.reverse();
+        Some(names.join(" / "))
+    }
+
+    /// Class whose hotkey was pressed this frame. Ignored while a text field has focus.
+    pub fn hotkey_pressed(&self, ctx: &egui::Context) -> Option<ClassId> {
+        if ctx.wants_keyboard_input() {
+            return None;
+        }
+        ctx.input(|i| {
+            self.classes
+                .iter()
+                .find(|c| {
+                    c.key()
+                        .is_some_and(|k| i.modifiers.is_none() && i.key_pressed(k))
+                })
+                .map(|c| c.id)
+        })
+    }
+
+    /// Combobox selecting a class or none. Returns true if the selection changed.
+    pub fn combo_box(
+        &self,
+        ui: &mut egui::Ui,
+        id_salt: impl std::hash::Hash,
+        selected: &mut Option<ClassId>,
+    ) -> bool {
+        let before = *selected;
+        let text = selected
+            .and_then(|id| self.path(id))
+            .unwrap_or_else(|| "Unlabeled".into());
+        ComboBox::from_id_salt(id_salt)
+            .selected_text(text)
+            .show_ui(ui, |ui| {
+                ui.selectable_value(selected, None, "Unlabeled");
+                for class in &self.classes {
+                    let [r, g, b] = class.color;
+                    let mut text = egui::RichText::new(self.path(class.id).unwrap_or_default())
+                        .color(egui::Color32::from_rgb(r, g, b));
+                    if let Some(hotkey) = &class.hotkey {
+                        text = text.strong();
+                        ui.selectable_value(selected, Some(class.id), text)
+                            .on_hover_text(format!("Hotkey: {hotkey}"));
+                    } else {
+                        ui.selectable_value(selected, Some(class.id), text);
+                    }
+                }
+            });
+        before != *selected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn class(id: u16, name: &str, parent: Option<u16>) -> LabelClass {
+        LabelClass {
+            id: ClassId(id),
+            name: name.into(),
+            color: [id as u8, 0, 0],
+            hotkey: None,
+            parent: parent.map(ClassId),
+        }
+    }
+
+    #[test]
+    fn hierarchy() {
+        let schema = LabelSchema::new(vec![
+            class(1, "vehicle", None),
+            class(2, "car", Some(1)),
+            class(3, "person", None),
+        ])
+        .unwrap();
+        assert_eq!(schema.path(ClassId(2)).as_deref(), Some("vehicle / car"));
+        assert!(schema.is_a(ClassId(2), ClassId(1)));
+        assert!(!schema.is_a(ClassId(3), ClassId(1)));
+        assert_eq!(schema.color(ClassId(3)), Some([3, 0, 0]));
+        assert_eq!(schema.path(ClassId(4)), None);
+    }
+
+    #[test]
+    fn invalid_schemas() {
+        assert_eq!(
+            LabelSchema::new(vec![class(1, "a", None), class(1, "b", None)]),
+            Err(LabelSchemaError::DuplicateId(ClassId(1)))
+        );
+        assert_eq!(
+            LabelSchema::new(vec![class(u16::MAX, "a", None)]),
+            Err(LabelSchemaError::ReservedId(ClassId(u16::MAX)))
+        );
+        assert_eq!(
+            LabelSchema::new(vec![class(1, "a", Some(2))]),
+            Err(LabelSchemaError::UnknownParent {
+                class: ClassId(1),
+                parent: ClassId(2)
+            })
+        );
+        assert_eq!(
+            LabelSchema::new(vec![class(1, "a", Some(2)), class(2, "b", Some(1))]),
+            Err(LabelSchemaError::Cycle(ClassId(1)))
+        );
+        let mut hotkey = class(1, "a", None);
+        hotkey.hotkey = Some("NoKey".into());
+        assert!(matches!(
+            LabelSchema::new(vec![hotkey]),
+            Err(LabelSchemaError::UnknownHotkey { .. })
+        ));
+    }
+}
@@ -0,0 +1,93 @@
+//! Every area of a [`MaskImage`] is a layer, which is drawn above the layers before it.
+
+use crate::MaskImage;
+
+/// Display and edit settings of a layer, stored with its area
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LayerSettings {
+    /// Empty for unnamed layers, which are shown by their index
+    pub name: String,
+    pub visible: bool,
+    /// Multiplied with the opacity of the [`crate::MaskSettings`]
+    pub opacity: u8,
+    /// Tools can't change the pixels or the class of locked layers
+    pub locked: bool,
+}
+
+impl Default for LayerSettings {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            visible: true,
+            opacity: u8::MAX,
+            locked: false,
+        }
+    }
+}
+
+impl LayerSettings {
+    pub fn display_name(&self, layer: usize) -> String {
+        if self.name.is_empty() {
+            format!("Layer {layer}")
+        } else {
+            self.name.clone()
+        }
+    }
+}
+
+/// List of the layers of a [`MaskImage`] to rename, hide, lock and activate them
+#[derive(Default)]
+pub struct LayerPanel {
+    /// Layer with settings being edited by a text field or slider, applied when editing stops.
+    /// This way, dragging the opacity slider adds a single history entry.
+    editing: Option<(usize, LayerSettings)>,
+}
+
+impl LayerPanel {
+    pub fn ui(&mut self, ui: &mut egui::Ui, masks: &mut MaskImage) {
+        let mut active = masks.active_layer();
+        ui.radio_value(&mut active, None, "New layer per area")
+            .on_hover_text("Add every new area as a layer of its own");
+
+        let layers = masks
+            .areas()
+            .iter()
+            .enumerate()
+            .filter_map(|(layer, area)| Some((layer, area.as_ref()?.layer.clone())))
+            .collect::<Vec<_>>();
+        for (layer, stored) in layers.into_iter().rev() {
+            let mut settings = match &self.editing {
+                Some((editing, settings)) if *editing == layer => settings.clone(),
+                _ => stored.clone(),
+            };
+            let mut commit = false;
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut active, Some(layer), "")
+                    .on_hover_text("Add new areas to this layer");
+                commit |= ui
+                    .toggle_value(&mut settings.visible, "👁")
+                    .on_hover_text("Visible")
+                    .changed();
+                commit |= ui
+                    .toggle_value(&mut settings.locked, "🔒")
+                    .on_hover_text("Locked")
+                    .changed();
+                let name = ui.add(
+                    egui::TextEdit::singleline(&mut settings.name)
+                        .hint_text(LayerSettings::default().display_name(layer))
+                        .desired_width(100.0),
+                );
+                let opacity = ui.add(egui::Slider::new(&mut settings.opacity, 0..=u8::MAX));
+                commit |= name.lost_focus() || opacity.drag_stopped();
+                if !commit && (name.changed() || opacity.changed()) {
+                    self.editing = Some((layer, settings.clone()));
+                }
+            });
+            if commit {
+                self.editing = None;
+                masks.set_layer_settings(layer, settings);
+            }
+        }
+        masks.set_active_layer(active);
+    }
+}
@@ -2,9 +2,15 @@ use std::num::NonZeroU32;
 use std::{future::Future, pin::Pin, sync::Arc};
 
 mod async_task;
+mod attribute;
 mod cursor_image;
+mod display_mode;
+mod filter;
+mod histogram;
 mod image_state;
 mod image_utils;
+mod label;
+mod layer;
 mod mask;
 mod pixel_range;
 mod state;
@@ -13,10 +19,16 @@ mod tools;
 mod viewer;
 
 pub use async_task::*;
+pub use attribute::*;
 pub use cursor_image::*;
+pub use display_mode::*;
+pub use filter::*;
+pub use histogram::*;
 pub use image_state::*;
 pub use image_utils::*;
 pub use imbuf::Image;
+pub use label::*;
+pub use layer::*;
 pub use state::*;
 
 pub type ToolTask = AsyncRefTask<Result<Box<dyn Tool>, String>>;
@@ -51,6 +63,14 @@ pub struct ImageData {
     pub id: ImageId,
     pub image: ImageLoadOk,
     pub masks: Vec<PixelArea>,
+    /// Registered images of the same scene (e.g. depth or IR) with the same width and height as `image`
+    pub auxiliary: Vec<AuxiliaryImage>,
+}
+
+#[derive(Clone)]
+pub struct AuxiliaryImage {
+    pub name: String,
+    pub image: ImageLoadOk,
 }
 
 impl ImageData {
@@ -58,6 +78,7 @@ impl ImageData {
         (0..2).map(|i| ImageData {
             id: ImageId::from(format!("image{}", i + 1).as_str()),
             masks: vec![],
+            auxiliary: vec![],
             image: {
                 let width = const { NonZeroU32::new(400).unwrap() };
                 let height = const { NonZeroU32::new(400).unwrap() };
@@ -79,6 +100,7 @@ impl ImageData {
                 ImageLoadOk {
                     original: crate::image_utils::OriginalImage::Rgb8(buffer.clone()),
                     adjust: buffer,
+                    orientation: crate::image_utils::ImageOrientation::NoTransforms,
                 }
             },
         })
@@ -1,50 +1,145 @@
-use std::{
-    collections::BinaryHeap,
-    iter::FusedIterator,
-    ops::{Range, RangeInclusive},
-};
+use std::{num::NonZeroU32, sync::Arc};
 
 use egui::{
     self, Color32, ColorImage, ImageSource, TextureHandle, TextureOptions, load::SizedTexture,
 };
-use imask::{ImageDimension, NonZeroRange, SanitizeSortedDisjoint, SortedRanges, SortedRangesMap};
+use imask::{ImageDimension, ImaskSet, NonZeroRange};
 use log::{debug, info};
-use range_set_blaze::SortedDisjointMap;
 
-use crate::{Meta, MetaRange, PixelArea};
+use crate::{
+    AreaKind, Attributes, ClassId, ColorLut, Connectivity, ImagePainter, LabelSchema,
+    LayerSettings, Meta, MetaRange, MorphologyOp, PixelArea, StructuringElement,
+};
 
 mod history;
+mod hit_test;
+mod materialized;
+mod palette;
 mod random_color;
 
 pub use history::*;
+use hit_test::HitIndex;
+use materialized::MaterializedState;
+pub use palette::*;
 pub use random_color::random_color_from_seed;
 
-pub struct Annotations(Vec<Option<PixelArea>>);
-#[derive(Debug, Eq, PartialEq)]
+/// Color of pixels without area, if [`MaskSettings::show_uncovered`] is set
+const UNCOVERED_COLOR: [u8; 3] = [255, 0, 255];
+/// Color of [`AreaKind::Ignore`] areas, which are drawn hatched
+pub const IGNORE_COLOR: [u8; 3] = [220, 220, 220];
+/// Ignore regions are drawn in diagonal stripes, half of this many image pixels wide
+const HATCH_PERIOD: usize = 8;
+
+/// Start and end of an outline segment in image coordinates
+type Segment = [[u32; 2]; 2];
+/// Outline segments of an area with the color they are drawn in
+type Outline = (Color32, Vec<Segment>);
+
+#[derive(Clone, Debug, PartialEq)]
 #[non_exhaustive]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 #[cfg_attr(feature = "serde", serde(default))]
 pub struct MaskSettings {
     pub default_opacity: u8,
+    pub overlay: OverlayMode,
+    /// Width of outlines in screen pixels, independent of the zoom
+    pub outline_width: f32,
+    /// Colors of the confidence in [`OverlayMode::Confidence`]
+    pub heatmap: ColorLut,
+    /// Colors of new unlabeled areas
+    pub palette: ColorPalette,
+    /// Highlights pixels which belong to no area, including hidden ones
+    pub show_uncovered: bool,
 }
 
 impl Default for MaskSettings {
     fn default() -> Self {
         Self {
             default_opacity: 128,
+            overlay: OverlayMode::default(),
+            outline_width: 1.5,
+            heatmap: ColorLut::Viridis,
+            palette: ColorPalette::default(),
+            show_uncovered: false,
+        }
+    }
+}
+
+/// How masks are drawn over the image
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum OverlayMode {
+    #[default]
+    Fill,
+    Outline,
+    FillAndOutline,
+    /// Every pixel is colored by its confidence instead of the color of its area
+    Confidence,
+}
+
+impl OverlayMode {
+    pub const ALL: [Self; 4] = [
+        Self::Fill,
+        Self::Outline,
+        Self::FillAndOutline,
+        Self::Confidence,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            OverlayMode::Fill => "Fill",
+            OverlayMode::Outline => "Outline",
+            OverlayMode::FillAndOutline => "Fill and outline",
+            OverlayMode::Confidence => "Confidence heatmap",
         }
     }
+
+    pub fn has_fill(self) -> bool {
+        matches!(
+            self,
+            OverlayMode::Fill | OverlayMode::FillAndOutline | OverlayMode::Confidence
+        )
+    }
+
+    pub fn has_outline(self) -> bool {
+        matches!(self, OverlayMode::Outline | OverlayMode::FillAndOutline)
+    }
+}
+
+/// How a new area treats pixels, which belong to existing areas already
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AddMode {
+    /// Only the pixels not covered by any area are added
+    #[default]
+    KeepExisting,
+    /// The pixels are removed from the existing areas and added completely
+    Overwrite,
 }
 
 pub struct MaskImage {
     size: [usize; 2],
-    annotations: Annotations,
+    state: MaterializedState,
     history: History,
+    /// Incremented whenever the areas change
+    revision: u64,
     texture_handle: Option<(bool, TextureHandle, ImageSource<'static>)>,
-    // Cannot remove handle immediately, as it might be used already previously in this epoch.
-    texture_handle_dirty: bool,
+    /// Region of the texture which has to be rendered again
+    texture_dirty: Option<TouchedRegion>,
+    /// Outline segments with their color by layer, computed on demand
+    outlines: Option<Vec<Option<Outline>>>,
+    /// Topmost layer by pixel, computed on demand
+    hit_index: Option<HitIndex>,
+    selected: Option<usize>,
+    /// Layer new areas are added to, instead of adding them as new layers
+    active_layer: Option<usize>,
     settings: MaskSettings,
     default_opacity_lut: [u8; 256],
+    labels: Arc<LabelSchema>,
+    /// Class assigned to new areas without a class
+    active_class: Option<ClassId>,
+    /// New areas become parts of the selected area
+    add_as_part: bool,
 }
 
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -58,17 +153,43 @@ impl MaskImage {
         let settings = MaskSettings::default();
         Self {
             size,
-            annotations: Annotations(annotations.into_iter().map(Some).collect()),
+            state: MaterializedState::new(annotations.into_iter().map(Some).collect(), &history),
             history,
+            revision: 0,
             texture_handle: None,
-            texture_handle_dirty: false,
+            texture_dirty: None,
+            outlines: None,
+            hit_index: None,
+            selected: None,
+            active_layer: None,
             default_opacity_lut: Self::build_opacity_lut(settings.default_opacity),
             settings: MaskSettings::default(),
+            labels: Default::default(),
+            active_class: None,
+            add_as_part: false,
         }
     }
 
+    pub fn settings(&self) -> &MaskSettings {
+        &self.settings
+    }
+
+    /// Redraws the masks only if settings affecting the fill changed
     pub fn set_settings(&mut self, settings: MaskSettings) {
-        self.default_opacity_lut = Self::build_opacity_lut(settings.default_opacity);
+        let rendered = |settings: &MaskSettings| {
+            (
+                settings.overlay.has_fill(),
+                (settings.overlay == OverlayMode::Confidence).then_some(settings.heatmap),
+                settings.show_uncovered,
+            )
+        };
+        if settings.default_opacity != self.settings.default_opacity {
+            self.default_opacity_lut = Self::build_opacity_lut(settings.default_opacity);
+            self.mark_dirty(Some(TouchedRegion::Everything));
+        }
+        if rendered(&settings) != rendered(&self.settings) {
+            self.mark_dirty(Some(TouchedRegion::Everything));
+        }
         self.settings = settings;
     }
 
@@ -77,51 +198,409 @@ impl MaskImage {
     }
 
     pub fn random_seed(&self) -> u16 {
-        (self.annotations.0.len() as u16).wrapping_add(self.history.random_seed())
+        (self.state.base_len() as u16).wrapping_add(self.history.random_seed())
     }
 
-    /// Generate the next color based on the current seed
+    /// Color of the active class, otherwise the next color of the palette based on the current
+    /// seed. Prefer [`Self::color_for`] once the new area is known.
     pub fn next_color(&self) -> [u8; 3] {
-        random_color_from_seed(self.random_seed())
+        self.class_color_or(|| self.allocate_color(&[]))
+    }
+
+    /// Color of the active class, otherwise a color of the palette which differs from the areas
+    /// touching `area`
+    pub fn color_for(&self, area: &PixelArea) -> [u8; 3] {
+        self.class_color_or(|| {
+            let neighbors = area
+                .dilate(StructuringElement::Square { radius: 1 }, self.image_size())
+                .map(|grown| {
+                    self.areas()
+                        .iter()
+                        .flatten()
+                        .filter(|other| other.layer.visible && grown.intersect(other).is_some())
+                        .map(|other| self.area_color(other))
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            self.allocate_color(&neighbors)
+        })
+    }
+
+    fn class_color_or(&self, allocate: impl FnOnce() -> [u8; 3]) -> [u8; 3] {
+        self.active_class
+            .and_then(|class| self.labels.color(class))
+            .unwrap_or_else(allocate)
+    }
+
+    fn allocate_color(&self, neighbors: &[[u8; 3]]) -> [u8; 3] {
+        let used = self
+            .areas()
+            .iter()
+            .flatten()
+            .map(|area| self.area_color(area))
+            .collect::<Vec<_>>();
+        self.settings
+            .palette
+            .pick(self.random_seed(), neighbors, &used)
+    }
+
+    pub fn labels(&self) -> &LabelSchema {
+        &self.labels
+    }
+
+    /// Redraws the masks only if `labels` is a different schema
+    pub fn set_labels(&mut self, labels: &Arc<LabelSchema>) {
+        if !Arc::ptr_eq(&self.labels, labels) {
+            self.labels = labels.clone();
+            self.mark_dirty(Some(TouchedRegion::Everything));
+        }
+    }
+
+    pub fn active_class(&self) -> Option<ClassId> {
+        self.active_class
+    }
+
+    pub fn set_active_class(&mut self, class: Option<ClassId>) {
+        self.active_class = class;
+    }
+
+    /// Color the area is drawn with: The color of its class or its own color if unlabeled.
+    /// Ignore regions are always drawn in [`IGNORE_COLOR`].
+    pub fn area_color(&self, area: &PixelArea) -> [u8; 3] {
+        if area.kind == AreaKind::Ignore {
+            return IGNORE_COLOR;
+        }
+        area.class
+            .and_then(|class| self.labels.color(class))
+            .unwrap_or(area.color)
+    }
+
+    /// Turns the area at `layer` into an ignore region or back, undoable
+    pub fn set_kind(&mut self, layer: usize, kind: AreaKind) {
+        if self.editable(layer).is_none_or(|area| area.kind == kind) {
+            return;
+        }
+        self.add_history_action(HistoryAction::SetKind(HistoryActionSetKind { layer, kind }));
+    }
+
+    /// Replaces the attributes of the area at `layer`, undoable
+    pub fn set_attributes(&mut self, layer: usize, attributes: Attributes) {
+        if self
+            .editable(layer)
+            .is_none_or(|area| area.attributes == attributes)
+        {
+            return;
+        }
+        self.add_history_action(HistoryAction::SetAttributes(HistoryActionSetAttributes {
+            layer,
+            attributes,
+        }));
+    }
+
+    /// Changes the class of the area at `layer`, undoable
+    pub fn set_class(&mut self, layer: usize, class: Option<ClassId>) {
+        if self.editable(layer).is_none() {
+            return;
+        }
+        self.add_history_action(HistoryAction::SetClass(HistoryActionSetClass {
+            layer,
+            class,
+        }));
     }
 
     pub fn sources(
         &mut self,
         ctx: &egui::Context,
     ) -> impl Iterator<Item = ImageSource<'static>> + '_ {
-        if self.texture_handle.is_none() || self.texture_handle_dirty {
-            self.texture_handle_dirty = false;
-
-            let texture_options = TextureOptions {
-                magnification: egui::TextureFilter::Nearest,
-                ..Default::default()
+        let texture_options = TextureOptions {
+            magnification: egui::TextureFilter::Nearest,
+            ..Default::default()
+        };
+        let full = [[0, 0], [self.size[0] - 1, self.size[1] - 1]];
+        let dirty = self.texture_dirty.take();
+        if self.texture_handle.is_none() {
+            let handle = ctx.load_texture("Overlays", self.render(full), texture_options);
+            let source = ImageSource::Texture(SizedTexture::from_handle(&handle));
+            self.texture_handle = Some((true, handle, source));
+        } else if let Some(region) = dirty {
+            // Only the touched region is uploaded again
+            let bounds = match region {
+                TouchedRegion::Bounds([min, max]) => {
+                    [min, [max[0].min(full[1][0]), max[1].min(full[1][1])]]
+                }
+                TouchedRegion::Everything => full,
             };
+            let image = self.render(bounds);
+            if let Some((visibility, handle, _)) = &mut self.texture_handle {
+                *visibility = true;
+                handle.set_partial(bounds[0], image, texture_options);
+            }
+        }
 
-            let mut pixels = vec![Color32::TRANSPARENT; self.size[0] * self.size[1]];
+        match &self.texture_handle {
+            Some((visibility, _, source))
+                if *visibility
+                    && (self.settings.overlay.has_fill() || self.settings.show_uncovered) =>
+            {
+                Some(source.clone()).into_iter()
+            }
+            _ => None.into_iter(),
+        }
+    }
 
-            for subgroups in self.subgroups().into_iter().flatten() {
-                let [r, g, b] = subgroups.color;
-                for (range, meta) in subgroups.pixels.iter::<Range<usize>>() {
-                    let a = self.default_opacity_lut[meta.confidence() as usize];
-                    let group_color = Color32::from_rgba_premultiplied(r, g, b, a);
-                    pixels[range].fill(group_color);
+    /// Overlay of all areas within the inclusive `[[min_x, min_y], [max_x, max_y]]`
+    fn render(&self, [min, max]: [[usize; 2]; 2]) -> ColorImage {
+        let width = max[0] + 1 - min[0];
+        let height = max[1] + 1 - min[1];
+        let mut pixels = vec![Color32::TRANSPARENT; width * height];
+        let heatmap = (self.settings.overlay == OverlayMode::Confidence)
+            .then(|| self.settings.heatmap.table());
+        let fill = self.settings.overlay.has_fill();
+        // Pixels of hidden areas are covered too
+        let mut covered = vec![
+            false;
+            if self.settings.show_uncovered {
+                width * height
+            } else {
+                0
+            }
+        ];
+
+        for subgroups in self.areas().iter().flatten() {
+            let roi = subgroups.roi();
+            let (roi_x, roi_y) = (roi.x as usize, roi.y as usize);
+            let draw = fill && subgroups.layer.visible;
+            if !(draw || self.settings.show_uncovered)
+                || roi_x > max[0]
+                || roi_y > max[1]
+                || roi_x + roi.width.get() as usize <= min[0]
+                || roi_y + roi.height.get() as usize <= min[1]
+            {
+                continue;
+            }
+            let area_color = self.area_color(subgroups);
+            for (y, x_range, meta) in subgroups.row_runs() {
+                let (y, start, end) = (y as usize, x_range.start as usize, x_range.end as usize);
+                if y < min[1] || y > max[1] || end <= min[0] || start > max[0] {
+                    continue;
+                }
+                let row_start = (y - min[1]) * width;
+                let run = row_start + start.max(min[0]) - min[0]
+                    ..row_start + end.min(max[0] + 1) - min[0];
+                if let Some(covered) = covered.get_mut(run.clone()) {
+                    covered.fill(true);
+                }
+                if !draw {
+                    continue;
+                }
+                let confidence = meta.confidence() as usize;
+                let ([r, g, b], alpha) = match &heatmap {
+                    Some(table) => (table[confidence], self.settings.default_opacity),
+                    None => (area_color, self.default_opacity_lut[confidence]),
+                };
+                let a = (alpha as u16 * subgroups.layer.opacity as u16 / u8::MAX as u16) as u8;
+                let color = Color32::from_rgba_premultiplied(r, g, b, a);
+                match subgroups.kind {
+                    AreaKind::Object => pixels[run].fill(color),
+                    AreaKind::Ignore => {
+                        let first_x = start.max(min[0]);
+                        for (x, pixel) in (first_x..).zip(&mut pixels[run]) {
+                            if (x + y) % HATCH_PERIOD < HATCH_PERIOD / 2 {
+                                *pixel = color;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        if self.settings.show_uncovered {
+            let [r, g, b] = UNCOVERED_COLOR;
+            let uncovered_color =
+                Color32::from_rgba_premultiplied(r, g, b, self.settings.default_opacity);
+            for (pixel, covered) in pixels.iter_mut().zip(covered) {
+                if !covered {
+                    *pixel = uncovered_color;
                 }
             }
+        }
+        ColorImage::new([width, height], pixels)
+    }
 
-            let handle = ctx.load_texture(
-                "Overlays",
-                ColorImage::new(self.size, pixels),
-                texture_options,
-            );
-            let source = ImageSource::Texture(SizedTexture::from_handle(&handle));
+    /// False if the overlay is hidden, e.g. with Ctrl+D
+    pub fn is_visible(&self) -> bool {
+        matches!(self.texture_handle, Some((true, _, _)))
+    }
 
-            self.texture_handle = Some((true, handle, source));
+    fn outlines(&mut self) -> &[Option<Outline>] {
+        if self.outlines.is_none() {
+            let outlines = self
+                .areas()
+                .iter()
+                .map(|area| {
+                    let area = area.as_ref().filter(|area| area.layer.visible)?;
+                    let [r, g, b] = self.area_color(area);
+                    Some((Color32::from_rgb(r, g, b), area.outline()))
+                })
+                .collect();
+            self.outlines = Some(outlines);
         }
+        self.outlines.as_deref().unwrap_or_default()
+    }
 
-        match &self.texture_handle {
-            Some((visibility, _, source)) if *visibility => Some(source.clone()).into_iter(),
-            _ => None.into_iter(),
+    /// Draws the outlines of all areas with a width in screen pixels, if enabled in the settings
+    pub fn paint_outlines(&mut self, painter: &ImagePainter) {
+        if !self.is_visible() || !self.settings.overlay.has_outline() {
+            return;
+        }
+        let width = self.settings.outline_width;
+        for (color, segments) in self.outlines().iter().flatten() {
+            paint_segments(painter, segments, |p, [start, end]| {
+                p.painter()
+                    .line_segment([start, end], egui::Stroke::new(width, *color));
+            });
+        }
+    }
+
+    /// Outlines the `hovered` area and the selected area with a dotted line
+    pub fn paint_highlights(&mut self, painter: &ImagePainter, hovered: Option<usize>) {
+        if !self.is_visible() {
+            return;
+        }
+        let width = self.settings.outline_width + 1.0;
+        let selected = self.selected();
+        let outlines = self.outlines();
+        if let Some(Some((_, segments))) = hovered.and_then(|layer| outlines.get(layer)) {
+            paint_segments(painter, segments, |p, [start, end]| {
+                p.painter()
+                    .line_segment([start, end], egui::Stroke::new(width, Color32::WHITE));
+            });
+        }
+        if let Some(Some((_, segments))) = selected.and_then(|layer| outlines.get(layer)) {
+            paint_segments(painter, segments, |p, [start, end]| {
+                p.draw_dotted_line(start, end);
+            });
+        }
+    }
+
+    /// Layer of the topmost area at the pixel
+    pub fn area_at(&mut self, pixel: [u32; 2]) -> Option<usize> {
+        if self.hit_index.is_none() {
+            self.hit_index = Some(HitIndex::new(self.areas(), self.size[1]));
+        }
+        self.hit_index.as_ref()?.get(pixel)
+    }
+
+    /// Layer of the selected area, if it still exists
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+            .filter(|layer| matches!(self.areas().get(*layer), Some(Some(_))))
+    }
+
+    pub fn set_selected(&mut self, layer: Option<usize>) {
+        self.selected = layer;
+    }
+
+    /// If set, new areas become parts of the selected area and are clipped to its pixels
+    pub fn add_as_part(&self) -> bool {
+        self.add_as_part
+    }
+
+    pub fn set_add_as_part(&mut self, add_as_part: bool) {
+        self.add_as_part = add_as_part;
+    }
+
+    /// Layer of the area the area at `layer` is a part of, if both exist
+    pub fn parent_of(&self, layer: usize) -> Option<usize> {
+        let parent = self.areas().get(layer)?.as_ref()?.parent?;
+        matches!(self.areas().get(parent), Some(Some(_))).then_some(parent)
+    }
+
+    /// Makes the area at `layer` a part of the area at `parent` or a top level area, undoable.
+    /// Returns false if an area doesn't exist, `layer` is locked or the hierarchy would be cyclic.
+    pub fn set_parent(&mut self, layer: usize, parent: Option<usize>) -> bool {
+        if self.editable(layer).is_none() {
+            return false;
+        }
+        if let Some(parent) = parent {
+            let ancestors = history::ancestors(self.areas(), Some(parent));
+            if ancestors.first() != Some(&parent) || ancestors.contains(&layer) {
+                return false;
+            }
+        }
+        if self.parent_of(layer) == parent {
+            return false;
+        }
+        self.add_history_action(HistoryAction::SetParent(HistoryActionSetParent {
+            layer,
+            parent,
+        }));
+        true
+    }
+
+    /// All areas without removed layers. Parents are adjusted to the new positions.
+    pub fn compacted(&self) -> Vec<PixelArea> {
+        let mut positions = vec![None; self.areas().len()];
+        for (position, (layer, _)) in self
+            .areas()
+            .iter()
+            .enumerate()
+            .filter(|(_, area)| area.is_some())
+            .enumerate()
+        {
+            positions[layer] = Some(position);
+        }
+        self.areas()
+            .iter()
+            .flatten()
+            .map(|area| {
+                let parent = area.parent.and_then(|parent| *positions.get(parent)?);
+                area.clone().with_parent(parent)
+            })
+            .collect()
+    }
+
+    /// Index, label, area and mean confidence of the area at `layer`
+    pub fn area_info_ui(&self, ui: &mut egui::Ui, layer: usize) {
+        let Some(Some(area)) = self.areas().get(layer) else {
+            return;
+        };
+        let [r, g, b] = self.area_color(area);
+        ui.colored_label(Color32::from_rgb(r, g, b), area.layer.display_name(layer));
+        if area.kind == AreaKind::Ignore {
+            ui.label("ignore region");
+        }
+        if let Some(parent) = self.parent_of(layer)
+            && let Some(Some(parent_area)) = self.areas().get(parent)
+        {
+            ui.label(format!(
+                "part of {}",
+                parent_area.layer.display_name(parent)
+            ));
+        }
+        if let Some(path) = area.class.and_then(|class| self.labels.path(class)) {
+            ui.label(path);
         }
+        ui.label(format!(
+            "area: {} px\nconfidence: {:.0}%",
+            area.pixel_count(),
+            area.mean_confidence() * 100.0
+        ));
+    }
+
+    fn mark_dirty(&mut self, region: Option<TouchedRegion>) {
+        let Some(region) = region else { return };
+        self.outlines = None;
+        self.hit_index = None;
+        self.texture_dirty = Some(match self.texture_dirty {
+            Some(dirty) => dirty.union(region),
+            None => region,
+        });
+    }
+
+    /// Changes whenever the areas change, e.g. by an edit, undo or redo. Values computed from the
+    /// areas can be cached until then.
+    pub fn revision(&self) -> u64 {
+        self.revision
     }
 
     pub fn is_dirty(&self) -> bool {
@@ -144,21 +623,42 @@ impl MaskImage {
         }
     }
 
+    /// Removes all areas except locked ones, undoable
     pub fn reset(&mut self) {
-        self.history.push(HistoryAction::Reset);
-        self.texture_handle_dirty = true;
+        self.add_history_action(HistoryAction::Reset);
     }
 
+    /// `ranges` are global positions in an image as wide as their bounds
     pub fn clear_ranges(
         &mut self,
         ranges: impl Iterator<Item = NonZeroRange<u64>> + ImageDimension,
     ) {
-        let action = HistoryAction::Clear(HistoryActionClear {
-            ranges: SortedRanges::try_from_ordered_iter(ranges).unwrap(),
+        let bounds = ranges.bounds();
+        let ranges = ranges
+            .map(|range| MetaRange {
+                range,
+                meta: Meta::default(),
+            })
+            .with_roi(bounds);
+        if let Some(area) = PixelArea::with_black_color(ranges) {
+            self.clear_area(area);
+        }
+    }
+
+    /// Removes the pixels of `area` from all unlocked areas
+    pub fn clear_area(&mut self, area: PixelArea) {
+        self.add_history_action(HistoryAction::Clear(HistoryActionClear {
+            area,
             layer: None,
-        });
+        }))
+    }
 
-        self.add_history_action(action)
+    /// Adds the area to `layer` or as a new area, pixels of existing areas are handled by `mode`
+    pub fn add_area_at(&mut self, area: PixelArea, layer: Option<usize>, mode: AddMode) {
+        match mode {
+            AddMode::KeepExisting => self.add_area_non_overlapping_parts_at(area, layer),
+            AddMode::Overwrite => self.add_area_overwriting_at(area, layer),
+        }
     }
 
     pub fn add_area_non_overlapping_parts(&mut self, subgroups: PixelArea) {
@@ -169,14 +669,17 @@ impl MaskImage {
         subgroups: PixelArea,
         layer: Option<usize>,
     ) {
-        let remaining = subgroups.map_inplace(|x| {
-            x.map_and_set_difference(SanitizeSortedDisjoint::new(
-                self.subgroups_ordered()
-                    .map(|x| RangeInclusive::<u64>::from(x.1.range)),
-            ))
-        });
+        // Parts overlap the areas they are part of
+        let ancestors = history::ancestors(self.areas(), self.part_parent());
+        let remaining = self
+            .areas()
+            .iter()
+            .enumerate()
+            .filter(|(layer, _)| !ancestors.contains(layer))
+            .filter_map(|(_, area)| area.as_ref())
+            .try_fold(subgroups, |rest, existing| rest.difference(existing));
         if let Some(x) = remaining {
-            self.add_area_overlapping_at(x, layer)
+            self.add_area_overlapping_at(x, layer);
         } else {
             debug!("All Pixels are in a other subgroup already");
         }
@@ -186,34 +689,273 @@ impl MaskImage {
         self.add_area_overlapping_at(subgroups, None);
     }
 
+    /// Adds the area to `layer`, otherwise to the active layer or as new layer.
+    /// Areas without a class get the active class.
     pub fn add_area_overlapping_at(&mut self, subgroups: PixelArea, layer: Option<usize>) {
+        let Some((pixel_area, layer)) = self.prepare_new_area(subgroups, layer) else {
+            return;
+        };
+        self.add_history_action(HistoryAction::Add(HistoryActionAdd { pixel_area, layer }))
+    }
+
+    /// Removes the pixels of the area from all other unlocked areas and adds it like
+    /// [`Self::add_area_overlapping_at`] as one undoable step. Pixels of locked areas are kept.
+    pub fn add_area_overwriting_at(&mut self, area: PixelArea, layer: Option<usize>) {
+        let Some((new_area, layer)) = self.prepare_new_area(area, layer) else {
+            return;
+        };
+        let remaining = self
+            .areas()
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| Some(*idx) != layer)
+            .filter_map(|(_, area)| area.as_ref().filter(|area| area.layer.locked))
+            .try_fold(new_area, |rest, locked| rest.difference(locked));
+        let Some(pixel_area) = remaining else {
+            debug!("All Pixels are in locked layers");
+            return;
+        };
+        self.add_history_action(HistoryAction::Overwrite(HistoryActionOverwrite {
+            pixel_area,
+            layer,
+        }))
+    }
+
+    /// Assigns the active class and layer and shows the hidden overlay again.
+    /// `None` if the target layer is locked.
+    fn prepare_new_area(
+        &mut self,
+        mut area: PixelArea,
+        layer: Option<usize>,
+    ) -> Option<(PixelArea, Option<usize>)> {
+        let layer = layer.or(self.active_layer());
+        if let Some(layer) = layer
+            && self.is_locked(layer)
+        {
+            debug!("Layer {layer} is locked");
+            return None;
+        }
+        if area.class.is_none() && area.kind == AreaKind::Object {
+            area.class = self.active_class;
+        }
+        if let Some(parent) = self.part_parent() {
+            let Some(Some(parent_area)) = self.areas().get(parent) else {
+                return None;
+            };
+            let Some(part) = area.intersect(parent_area) else {
+                debug!("New area is outside of the parent area {parent}");
+                return None;
+            };
+            area = part.with_parent(Some(parent));
+        }
         if let Some((visibility @ false, _, _)) = &mut self.texture_handle {
             *visibility = true;
         }
-        self.add_history_action(HistoryAction::Add(HistoryActionAdd {
-            pixel_area: subgroups,
+        Some((area, layer))
+    }
+
+    /// Layer new areas are added as parts of
+    fn part_parent(&self) -> Option<usize> {
+        self.selected().filter(|_| self.add_as_part)
+    }
+
+    /// Area at `layer`, if it exists and isn't locked
+    fn editable(&self, layer: usize) -> Option<&PixelArea> {
+        self.areas()
+            .get(layer)?
+            .as_ref()
+            .filter(|area| !area.layer.locked)
+    }
+
+    pub fn is_locked(&self, layer: usize) -> bool {
+        matches!(self.areas().get(layer), Some(Some(area)) if area.layer.locked)
+    }
+
+    pub fn layer_settings(&self, layer: usize) -> Option<&LayerSettings> {
+        Some(&self.areas().get(layer)?.as_ref()?.layer)
+    }
+
+    /// Changes name, visibility, opacity or lock of the area at `layer`, undoable
+    pub fn set_layer_settings(&mut self, layer: usize, settings: LayerSettings) {
+        if self
+            .layer_settings(layer)
+            .is_some_and(|current| *current != settings)
+        {
+            self.add_history_action(HistoryAction::SetLayer(HistoryActionSetLayer {
+                layer,
+                settings,
+            }));
+        }
+    }
+
+    /// Layer new areas are added to, if it still exists. `None` adds them as new layers.
+    pub fn active_layer(&self) -> Option<usize> {
+        self.active_layer
+            .filter(|layer| self.layer_settings(*layer).is_some())
+    }
+
+    pub fn set_active_layer(&mut self, layer: Option<usize>) {
+        self.active_layer = layer;
+    }
+
+    /// Replaces the area at `layer` with the result of `f`, undoable.
+    /// Returns false if there is no area at `layer` or it is locked.
+    pub fn replace_area(
+        &mut self,
+        layer: usize,
+        f: impl FnOnce(&PixelArea, [NonZeroU32; 2]) -> Option<PixelArea>,
+    ) -> bool {
+        let Some(area) = self.editable(layer) else {
+            return false;
+        };
+        let pixel_area = f(area, self.image_size());
+        self.add_history_action(HistoryAction::Replace(HistoryActionReplace {
             layer,
-        }))
+            pixel_area,
+        }));
+        true
     }
 
-    pub fn add_history_action(&mut self, action: HistoryAction) {
-        if let Some(x) = self.annotations.0.iter().find_map(|a| a.as_ref()) {
-            match &action {
-                HistoryAction::Add(add) => assert_eq!(
-                    add.pixel_area.pixels.bounds().width,
-                    x.pixels.bounds().width,
-                    "Imanot cannot handle pixel_area with different sizes yet",
-                ),
-                HistoryAction::Reset => {}
-                HistoryAction::Clear(clear) => assert_eq!(
-                    clear.ranges.bounds().width,
-                    x.pixels.bounds().width,
-                    "Imanot cannot handle pixel_area with different sizes yet",
-                ),
-            }
+    pub fn apply_morphology(
+        &mut self,
+        layer: usize,
+        op: MorphologyOp,
+        element: StructuringElement,
+    ) -> bool {
+        self.replace_area(layer, |area, size| area.morphology(op, element, size))
+    }
+
+    /// Keeps the first island of the area at `layer` and adds the others as new areas, undoable.
+    /// Returns the number of islands.
+    pub fn split_components(&mut self, layer: usize, connectivity: Connectivity) -> usize {
+        let Some(area) = self.editable(layer) else {
+            return 0;
+        };
+        let parts = area.connected_components(connectivity);
+        let count = parts.len();
+        if count > 1 {
+            self.add_history_action(HistoryAction::Split(HistoryActionSplit { layer, parts }));
+        }
+        count
+    }
+
+    /// Splits the area at `layer` along the line through `line` in image coordinates, undoable.
+    /// The part left of the line, looking from its start to its end, is added as new area.
+    /// Returns false if the line doesn't cross the area or it is locked.
+    pub fn split_along_line(&mut self, layer: usize, line: [[f32; 2]; 2]) -> bool {
+        let Some(area) = self.editable(layer) else {
+            return false;
+        };
+        let Some(parts) = area.split_by_line(line) else {
+            return false;
+        };
+        self.add_history_action(HistoryAction::Split(HistoryActionSplit {
+            layer,
+            parts: parts.into(),
+        }));
+        true
+    }
+
+    /// Swaps the area at `layer` with the next area drawn above (`up`) or below it, undoable.
+    /// Returns false if there is no such area.
+    pub fn move_area(&mut self, layer: usize, up: bool) -> bool {
+        let areas = self.areas();
+        if !matches!(areas.get(layer), Some(Some(_))) {
+            return false;
+        }
+        let exists = |other: &usize| areas[*other].is_some();
+        let other = if up {
+            (layer + 1..areas.len()).find(exists)
+        } else {
+            (0..layer).rev().find(exists)
+        };
+        let Some(other) = other else {
+            return false;
+        };
+        let swap = HistoryAction::Swap(HistoryActionSwap {
+            layers: [layer, other],
+        });
+        self.selected = swapped(self.selected, &swap);
+        self.add_history_action(swap);
+        true
+    }
+
+    /// Adds the areas at `others` to the area at `layer` and removes them, undoable.
+    /// The merged area keeps the color, class and layer settings of `layer`. Locked areas are
+    /// not merged, neither are areas `layer` is a part of, as it would become a part of itself.
+    /// Returns false if there is nothing to merge.
+    pub fn merge_areas(&mut self, layer: usize, others: &[usize]) -> bool {
+        let exists = |layer: &usize| self.editable(*layer).is_some();
+        let ancestors = history::ancestors(self.areas(), self.parent_of(layer));
+        let others = others
+            .iter()
+            .copied()
+            .filter(|other| *other != layer && exists(other) && !ancestors.contains(other))
+            .collect::<Vec<_>>();
+        if !exists(&layer) || others.is_empty() {
+            return false;
+        }
+        self.add_history_action(HistoryAction::Merge(HistoryActionMerge { layer, others }));
+        true
+    }
+
+    /// Changes the confidence of all pixels of unlocked areas within `footprint`, undoable.
+    /// Returns false if no such pixel exists.
+    pub fn adjust_confidence(&mut self, footprint: PixelArea, delta: i16) -> bool {
+        let touches = self
+            .areas()
+            .iter()
+            .flatten()
+            .any(|area| !area.layer.locked && area.intersect(&footprint).is_some());
+        if delta == 0 || !touches {
+            return false;
         }
+        self.add_history_action(HistoryAction::AdjustConfidence(
+            HistoryActionAdjustConfidence {
+                area: footprint,
+                delta,
+            },
+        ));
+        true
+    }
+
+    /// [width, height] of the image
+    pub fn image_size(&self) -> [NonZeroU32; 2] {
+        self.size
+            .map(|x| NonZeroU32::new(x as u32).expect("Images are not empty"))
+    }
+
+    pub fn add_history_action(&mut self, action: HistoryAction) {
+        self.mark_dirty(action.touched_region(self.areas()));
         self.history.push(action);
-        self.texture_handle_dirty = true;
+        self.state.pushed(&self.history);
+        self.revision += 1;
+    }
+
+    pub fn undo(&mut self) {
+        if let Some(action) = self.history.undo() {
+            self.selected = swapped(self.selected, action);
+            self.state.rebuild(&self.history);
+            self.revision += 1;
+            let touched = self
+                .history
+                .peek_redo()
+                .and_then(|action| action.touched_region(self.state.current()));
+            self.mark_dirty(touched);
+        }
+    }
+
+    pub fn redo(&mut self) {
+        let touched = self
+            .history
+            .peek_redo()
+            .and_then(|action| action.touched_region(self.state.current()));
+        if let Some(action) = self.history.redo() {
+            self.selected = swapped(self.selected, action);
+            self.state.redone(action);
+            self.revision += 1;
+            self.mark_dirty(touched);
+        }
     }
 
     pub fn handle_events(&mut self, ctx: &egui::Context) {
@@ -226,16 +968,13 @@ impl MaskImage {
         });
 
         if cmd_z_pressed {
-            let require_redraw = if shift_pressed {
+            if shift_pressed {
                 info!("Redo");
-                self.history.redo().is_some()
+                self.redo();
             } else {
                 info!("Undo");
-                self.history.undo().is_some()
-            };
-            if require_redraw {
-                self.texture_handle_dirty = true;
-            };
+                self.undo();
+            }
         }
         if let Some((visible, _, _)) = &mut self.texture_handle
             && cmd_d_pressed
@@ -244,71 +983,81 @@ impl MaskImage {
         }
     }
 
-    pub fn subgroups(&self) -> Vec<Option<PixelArea>> {
-        let base = self.annotations.0.clone();
-        self.history.iter().fold(base, |acc, r| r.apply(acc))
+    /// Pixels of the image which belong to no area, including hidden ones
+    pub fn uncovered(&self) -> Option<PixelArea> {
+        let [width, height] = self.size.map(|x| x as u64);
+        let image = PixelArea::from_row_runs(
+            (0..height).map(|y| (y, 0..width, Meta::default())),
+            [0, 0, 0],
+        )?;
+        self.areas()
+            .iter()
+            .flatten()
+            .try_fold(image, |rest, area| rest.difference(area))
     }
 
-    fn subgroups_ordered(&self) -> impl Iterator<Item = (usize, MetaRange)> + FusedIterator + '_ {
-        struct HeapItem<T>(MetaRange, usize, T);
-
-        impl<T> Eq for HeapItem<T> {}
-        impl<T> PartialEq for HeapItem<T> {
-            fn eq(&self, other: &Self) -> bool {
-                self.0 == other.0
-            }
-        }
-        impl<T> PartialOrd for HeapItem<T> {
-            fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-                Some(self.cmp(other))
-            }
-        }
-        impl<T> Ord for HeapItem<T> {
-            fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-                self.0.range.start.cmp(&other.0.range.start).reverse()
-            }
-        }
-
-        struct GroupIterator(
-            BinaryHeap<HeapItem<<SortedRangesMap<u32, u32, Vec<Meta>> as IntoIterator>::IntoIter>>,
-        );
-
-        let x: BinaryHeap<_> = self
-            .subgroups()
+    /// First 4-connected uncovered region with at least `min_pixels` pixels, whose first pixel
+    /// comes after `after` in reading order. Wraps around to the first region.
+    pub fn next_gap(&self, after: Option<[u32; 2]>, min_pixels: u64) -> Option<PixelArea> {
+        let reading_order = |[x, y]: [u32; 2]| (y, x);
+        let gaps = self
+            .uncovered()?
+            .connected_components(Connectivity::Four)
             .into_iter()
-            .enumerate()
-            .filter_map(|(group_id, opt_x)| {
-                let x = opt_x?;
-                let mut iter = x.pixels.into_iter();
-                Some(HeapItem(iter.next()?, group_id, iter))
-            })
-            .collect();
+            .filter(|gap| gap.pixel_count() >= min_pixels)
+            .collect::<Vec<_>>();
+        let next = gaps.iter().position(|gap| {
+            after.is_none_or(|after| reading_order(gap.first_pixel()) > reading_order(after))
+        });
+        gaps.into_iter().nth(next.unwrap_or(0))
+    }
 
-        impl Iterator for GroupIterator {
-            type Item = (usize, MetaRange);
+    /// Current areas by layer, `None` for removed layers
+    pub fn areas(&self) -> &[Option<PixelArea>] {
+        self.state.current()
+    }
 
-            fn next(&mut self) -> Option<Self::Item> {
-                if let Some(HeapItem(subgroup, group_id, mut rest)) = self.0.pop() {
-                    if let Some(x) = rest.next() {
-                        self.0.push(HeapItem(x, group_id, rest));
-                    }
-                    Some((group_id, subgroup))
-                } else {
-                    None
-                }
+    pub fn subgroups(&self) -> Vec<Option<PixelArea>> {
+        self.areas().to_vec()
+    }
+}
+
+/// Layer of the `selected` area after applying or reverting `action`, which keeps the same area
+/// selected if it is swapped
+fn swapped(selected: Option<usize>, action: &HistoryAction) -> Option<usize> {
+    match action {
+        HistoryAction::Swap(HistoryActionSwap { layers: [a, b] }) => selected.map(|selected| {
+            if selected == *a {
+                *b
+            } else if selected == *b {
+                *a
+            } else {
+                selected
             }
+        }),
+        _ => selected,
+    }
+}
+
+/// Draws the segments in image coordinates, which are visible on the screen
+fn paint_segments(
+    painter: &ImagePainter,
+    segments: &[Segment],
+    mut paint: impl FnMut(&ImagePainter, [egui::Pos2; 2]),
+) {
+    let clip_rect = painter.painter().clip_rect();
+    for [start, end] in segments {
+        let [start, end] =
+            [start, end].map(|[x, y]| painter.image_to_screen(egui::pos2(*x as f32, *y as f32)));
+        if clip_rect.intersects(egui::Rect::from_two_pos(start, end)) {
+            paint(painter, [start, end]);
         }
-        // self.0.pop() at some point relies on Vec::pop(), which can be called multiple times
-        impl FusedIterator for GroupIterator {}
-        GroupIterator(x)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use imask::{ImaskSet, NonZeroRange};
-
-    use crate::{CreateTotal, MetaRange};
+    use crate::CreateTotal;
 
     use super::*;
     use std::num::{NonZero, NonZeroU32};
@@ -319,7 +1068,6 @@ mod tests {
     const NON_ZERO_4: NonZero<u32> = NonZero::new(4).unwrap();
     const NON_ZERO_5: NonZero<u32> = NonZero::new(5).unwrap();
     const NON_ZERO_6: NonZero<u32> = NonZero::new(6).unwrap();
-    const NON_ZERO_7: NonZero<u32> = NonZero::new(7).unwrap();
     const NON_ZERO_8: NonZero<u32> = NonZero::new(8).unwrap();
 
     const WIDTH_10: NonZero<u32> = NonZero::new(10).unwrap();
@@ -504,40 +1252,225 @@ mod tests {
     }
 
     #[test]
-    fn iter_sorted() {
-        let mut history = History::default();
-        history.push(HistoryAction::Add(HistoryActionAdd {
-            pixel_area: PixelArea::with_black_color(
-                [
-                    MetaRange::new_total(22, NON_ZERO_7.into()),
-                    MetaRange::new_total(39, NON_ZERO_1.into()),
-                    MetaRange::new_total(42, NON_ZERO_7.into()),
-                ]
-                .with_bounds(WIDTH_10, NON_ZERO_1),
-            )
-            .unwrap(),
-            layer: None,
-        }));
-        let x = MaskImage::new(
-            [10, 10],
+    fn partial_render_matches_full_render() {
+        let mut mask_image = build_mask_10([(1, NON_ZERO_8), (4, NON_ZERO_2)]);
+        mask_image.add_area_overlapping(PixelArea::single_range_total_black(
+            3, 2, NON_ZERO_3, WIDTH_10,
+        ));
+        let full = mask_image.render([[0, 0], [9, 9]]);
+        let partial = mask_image.render([[2, 0], [5, 2]]);
+        assert_eq!(partial.size, [4, 3]);
+        for y in 0..3 {
+            assert_eq!(
+                partial.pixels[y * 4..y * 4 + 4],
+                full.pixels[y * 10 + 2..y * 10 + 6]
+            );
+        }
+        assert_ne!(partial.pixels[0], Color32::TRANSPARENT);
+    }
+
+    #[test]
+    fn next_gap_skips_small_gaps_and_wraps() {
+        // Row 0 has uncovered pixels at 0 and 5..=9, all other rows are uncovered
+        let mut mask_image = build_mask_10([(1, NON_ZERO_4)]);
+        mask_image.add_area_overlapping(PixelArea::single_range_total_black(
+            0, 1, WIDTH_10, WIDTH_10,
+        ));
+        assert_eq!(mask_image.uncovered().unwrap().pixel_count(), 86);
+
+        let first = mask_image.next_gap(None, 2).unwrap();
+        assert_eq!(first.first_pixel(), [5, 0]);
+        assert_eq!(first.pixel_count(), 5);
+        let second = mask_image.next_gap(Some(first.first_pixel()), 2).unwrap();
+        assert_eq!(second.first_pixel(), [0, 2]);
+        assert_eq!(second.pixel_count(), 80);
+        let wrapped = mask_image.next_gap(Some(second.first_pixel()), 2).unwrap();
+        assert_eq!(wrapped, first);
+        assert_eq!(mask_image.next_gap(None, 1).unwrap().first_pixel(), [0, 0]);
+    }
+
+    #[test]
+    fn ignore_regions_stay_free_of_new_masks() {
+        let mut mask_image = MaskImage::new([10, 10], vec![], History::default());
+        mask_image.set_active_class(Some(ClassId(1)));
+        mask_image.add_area_non_overlapping_parts(
+            PixelArea::single_range_total_black(0, 0, NON_ZERO_4, WIDTH_10)
+                .with_kind(AreaKind::Ignore),
+        );
+        mask_image.add_area_at(
+            PixelArea::single_range_total_black(2, 0, NON_ZERO_4, WIDTH_10),
+            None,
+            AddMode::KeepExisting,
+        );
+        let areas = mask_image.subgroups();
+        let ignore = areas[0].as_ref().unwrap();
+        assert_eq!(ignore.class, None);
+        assert_eq!(mask_image.area_color(ignore), IGNORE_COLOR);
+        assert_eq!(areas[1].as_ref().unwrap().first_pixel(), [4, 0]);
+
+        // Hatched: pixels (0, 0)..(3, 0) are in the first stripe, the rest of the row isn't
+        let rendered = mask_image.render([[0, 0], [9, 0]]);
+        assert!(
+            rendered.pixels[..4]
+                .iter()
+                .all(|p| *p != Color32::TRANSPARENT)
+        );
+        mask_image.set_kind(1, AreaKind::Ignore);
+        let rendered = mask_image.render([[0, 0], [9, 0]]);
+        assert!(
+            rendered.pixels[4..6]
+                .iter()
+                .all(|p| *p == Color32::TRANSPARENT)
+        );
+    }
+
+    #[test]
+    fn parts_are_clipped_to_selected_area() {
+        let mut mask_image = build_mask_10([(2, NON_ZERO_4), (7, NON_ZERO_2)]);
+        mask_image.set_selected(Some(0));
+        mask_image.set_add_as_part(true);
+        mask_image.add_area_at(
+            PixelArea::single_range_total_black(0, 0, WIDTH_10, WIDTH_10),
+            None,
+            AddMode::KeepExisting,
+        );
+        let part = mask_image.areas()[2].clone().unwrap();
+        assert_eq!(part.parent, Some(0));
+        assert_eq!((part.first_pixel(), part.pixel_count()), ([2, 0], 4));
+        assert_eq!(mask_image.areas()[0].as_ref().unwrap().pixel_count(), 4);
+
+        assert!(!mask_image.set_parent(0, Some(2)), "Cycles are rejected");
+        assert!(mask_image.set_parent(1, Some(2)));
+        mask_image.add_area_at(
+            PixelArea::single_range_total_black(0, 1, NON_ZERO_2, WIDTH_10),
+            None,
+            AddMode::KeepExisting,
+        );
+        assert_eq!(
+            mask_image.areas().len(),
+            3,
+            "Areas outside the parent are rejected"
+        );
+
+        mask_image.replace_area(0, |_, _| None);
+        let compacted = mask_image.compacted();
+        assert_eq!(
+            compacted.iter().map(|area| area.parent).collect::<Vec<_>>(),
+            [Some(1), None]
+        );
+    }
+
+    #[test]
+    fn locked_layers_are_kept() {
+        let mut mask_image = build_mask_10([(0, NON_ZERO_4)]);
+        let locked = LayerSettings {
+            locked: true,
+            ..Default::default()
+        };
+        mask_image.set_layer_settings(0, locked.clone());
+        mask_image.add_area_overwriting_at(
+            PixelArea::single_range_total_black(2, 0, NON_ZERO_4, WIDTH_10),
+            None,
+        );
+        mask_image.clear_area(PixelArea::single_range_total_black(
+            0, 0, WIDTH_10, WIDTH_10,
+        ));
+        assert_eq!(
+            mask_image.subgroups(),
             vec![
-                PixelArea::with_black_color(
-                    [
-                        MetaRange::new_total(2, NON_ZERO_5.into()),
-                        MetaRange::new_total(12, NON_ZERO_5.into()),
-                    ]
-                    .with_bounds(WIDTH_10, NON_ZERO_1),
-                )
-                .unwrap(),
-                PixelArea::single_range_total_black(32, 0, NON_ZERO_5, WIDTH_10),
-            ],
-            history,
+                Some(
+                    PixelArea::single_range_total_black(0, 0, NON_ZERO_4, WIDTH_10)
+                        .with_layer(locked)
+                ),
+                None
+            ]
+        );
+
+        mask_image.set_active_layer(Some(0));
+        mask_image.add_area_overlapping(PixelArea::single_range_total_black(
+            6, 0, NON_ZERO_2, WIDTH_10,
+        ));
+        assert_eq!(mask_image.subgroups().len(), 2);
+    }
+
+    #[test]
+    fn areas_are_not_merged_into_their_parts() {
+        // C (2) is part of A (1), which is part of D (0)
+        let mut mask_image = build_mask_10([(0, NON_ZERO_2), (2, NON_ZERO_2), (4, NON_ZERO_2)]);
+        assert!(mask_image.set_parent(1, Some(0)));
+        assert!(mask_image.set_parent(2, Some(1)));
+        assert!(!mask_image.merge_areas(2, &[0]));
+        assert!(!mask_image.merge_areas(2, &[0, 1]));
+        assert_eq!(mask_image.parent_of(1), Some(0));
+        assert_eq!(mask_image.parent_of(2), Some(1));
+
+        assert!(mask_image.merge_areas(0, &[2]));
+        assert_eq!(mask_image.areas()[2], None);
+        assert_eq!(mask_image.parent_of(1), Some(0));
+        assert_eq!(mask_image.parent_of(0), None);
+    }
+
+    #[test]
+    fn selection_follows_swapped_areas() {
+        let mut mask_image = build_mask_10([(0, NON_ZERO_2), (4, NON_ZERO_2)]);
+        mask_image.set_selected(Some(1));
+        assert!(mask_image.move_area(0, true));
+        assert_eq!(mask_image.selected(), Some(0));
+        assert_eq!(
+            mask_image.areas()[0].as_ref().unwrap().first_pixel(),
+            [4, 0]
+        );
+
+        mask_image.undo();
+        assert_eq!(mask_image.selected(), Some(1));
+        mask_image.redo();
+        assert_eq!(mask_image.selected(), Some(0));
+        assert_eq!(
+            mask_image.areas()[0].as_ref().unwrap().first_pixel(),
+            [4, 0]
         );
-        let group_sequence: Vec<_> = x
-            .subgroups_ordered()
-            .map(|(group_id, _)| group_id)
-            .collect();
-        assert_eq!(group_sequence, vec![0, 0, 2, 1, 2, 2]);
+    }
+
+    #[test]
+    fn new_areas_get_active_class_color() {
+        let labels = Arc::new(
+            LabelSchema::new(vec![crate::LabelClass {
+                id: ClassId(7),
+                name: "car".into(),
+                color: [1, 2, 3],
+                hotkey: None,
+                parent: None,
+            }])
+            .unwrap(),
+        );
+        let mut mask_image = MaskImage::new([10, 10], vec![], History::default());
+        mask_image.set_labels(&labels);
+        mask_image.set_active_class(Some(ClassId(7)));
+        assert_eq!(mask_image.next_color(), [1, 2, 3]);
+        mask_image.add_area_overlapping(PixelArea::single_range_total_black(
+            1, 0, NON_ZERO_2, WIDTH_10,
+        ));
+        let area = mask_image.subgroups()[0].clone().unwrap();
+        assert_eq!(area.class, Some(ClassId(7)));
+        assert_eq!(mask_image.area_color(&area), [1, 2, 3]);
+
+        mask_image.set_class(0, None);
+        let area = mask_image.subgroups()[0].clone().unwrap();
+        assert_eq!(mask_image.area_color(&area), [0, 0, 0]);
+    }
+
+    #[test]
+    fn new_colors_differ_from_touching_areas() {
+        let mut mask_image = MaskImage::new([10, 10], vec![], History::default());
+        let mut settings = MaskSettings::default();
+        settings.palette = ColorPalette::OkabeIto;
+        mask_image.set_settings(settings);
+        let mut existing = PixelArea::single_range_total_black(0, 0, NON_ZERO_2, WIDTH_10);
+        existing.color = mask_image.next_color();
+        mask_image.add_area_overlapping(existing.clone());
+
+        let touching = PixelArea::single_range_total_black(1, 1, NON_ZERO_2, WIDTH_10);
+        assert_ne!(mask_image.color_for(&touching), existing.color);
     }
 
     #[test]
@@ -564,10 +1497,11 @@ mod tests {
             )
             .unwrap(),
         );
-        let group_sequence: Vec<_> = x
-            .subgroups_ordered()
-            .map(|(group_id, _)| group_id)
-            .collect();
-        assert_eq!(group_sequence, vec![0, 1, 2]);
+        assert_eq!(
+            x.subgroups()[2],
+            Some(PixelArea::single_range_total_black(
+                5, 0, NON_ZERO_1, WIDTH_10
+            ))
+        );
     }
 }
@@ -2,13 +2,7 @@
 //! There is no undo on Vec<SubGroups>, but the original Vec<SubGroup> can be converted multiple times to get the Aggregated result.
 //! This way, a we don't need to implement undo, which would require additional infos in HistoryAction
 
-use std::ops::RangeInclusive;
-
-use imask::{ImageDimension, SortedRanges};
-use itertools::Itertools;
-use range_set_blaze::SortedDisjointMap;
-
-use crate::{Meta, PixelArea};
+use crate::{AreaKind, Attributes, ClassId, LayerSettings, Meta, PixelArea};
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct HistoryActionAdd {
@@ -16,104 +10,385 @@ pub struct HistoryActionAdd {
     pub layer: Option<usize>,
 }
 
+/// Removes the pixels of `pixel_area` from all other unlocked areas except its parent and their
+/// parents, then adds it like [`HistoryActionAdd`]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HistoryActionOverwrite {
+    pub pixel_area: PixelArea,
+    pub layer: Option<usize>,
+}
+
+/// Removes the pixels of `area` from all unlocked areas or only from `layer`
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct HistoryActionClear {
-    pub ranges: SortedRanges<u64, u64>,
+    pub area: PixelArea,
     pub layer: Option<usize>,
 }
 
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HistoryActionSetClass {
+    pub layer: usize,
+    pub class: Option<ClassId>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HistoryActionSetKind {
+    pub layer: usize,
+    pub kind: AreaKind,
+}
+
+/// Makes the area at `layer` a part of the area at `parent`, or a top level area
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HistoryActionSetParent {
+    pub layer: usize,
+    pub parent: Option<usize>,
+}
+
+/// Replaces all attributes of the area at `layer`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HistoryActionSetAttributes {
+    pub layer: usize,
+    pub attributes: Attributes,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HistoryActionSetLayer {
+    pub layer: usize,
+    pub settings: LayerSettings,
+}
+
+/// Replaces the area at `layer`, e.g. with the result of a morphological operation
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HistoryActionReplace {
+    pub layer: usize,
+    pub pixel_area: Option<PixelArea>,
+}
+
+/// Replaces the area at `layer` with the first part. The other parts are appended as new areas.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HistoryActionSplit {
+    pub layer: usize,
+    pub parts: Vec<PixelArea>,
+}
+
+/// Exchanges the areas of two layers, which changes their drawing order
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HistoryActionSwap {
+    pub layers: [usize; 2],
+}
+
+/// Adds the areas of `others` to the area at `layer` and removes them
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HistoryActionMerge {
+    pub layer: usize,
+    pub others: Vec<usize>,
+}
+
+/// Changes the confidence of all unlocked pixels within `area` by `delta`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HistoryActionAdjustConfidence {
+    pub area: PixelArea,
+    pub delta: i16,
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum HistoryAction {
     Add(HistoryActionAdd),
+    Overwrite(HistoryActionOverwrite),
+    /// Removes all areas except locked ones
     Reset,
     Clear(HistoryActionClear),
+    SetClass(HistoryActionSetClass),
+    SetKind(HistoryActionSetKind),
+    SetParent(HistoryActionSetParent),
+    SetAttributes(HistoryActionSetAttributes),
+    SetLayer(HistoryActionSetLayer),
+    Replace(HistoryActionReplace),
+    Split(HistoryActionSplit),
+    Swap(HistoryActionSwap),
+    Merge(HistoryActionMerge),
+    AdjustConfidence(HistoryActionAdjustConfidence),
+}
+
+/// Part of the image whose rendering may change by applying or reverting an action
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TouchedRegion {
+    /// Inclusive pixel bounds as [[min_x, min_y], [max_x, max_y]]
+    Bounds([[usize; 2]; 2]),
+    Everything,
+}
+
+impl TouchedRegion {
+    pub fn of(area: &PixelArea) -> Self {
+        let roi = area.roi();
+        let [x, y] = [roi.x as usize, roi.y as usize];
+        Self::Bounds([
+            [x, y],
+            [
+                x + roi.width.get() as usize - 1,
+                y + roi.height.get() as usize - 1,
+            ],
+        ])
+    }
+
+    pub fn union(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::Bounds([a_min, a_max]), Self::Bounds([b_min, b_max])) => Self::Bounds([
+                [a_min[0].min(b_min[0]), a_min[1].min(b_min[1])],
+                [a_max[0].max(b_max[0]), a_max[1].max(b_max[1])],
+            ]),
+            _ => Self::Everything,
+        }
+    }
 }
 
 impl HistoryAction {
+    /// Region which differs between `unapplied`, the areas without this action, and the areas
+    /// after applying it. `None` if nothing changes.
+    pub fn touched_region(&self, unapplied: &[Option<PixelArea>]) -> Option<TouchedRegion> {
+        let area_at = |layer: usize| unapplied.get(layer).and_then(Option::as_ref);
+        match self {
+            HistoryAction::Add(add) => Some(TouchedRegion::of(&add.pixel_area)),
+            HistoryAction::Overwrite(overwrite) => Some(TouchedRegion::of(&overwrite.pixel_area)),
+            HistoryAction::Reset => Some(TouchedRegion::Everything),
+            HistoryAction::Clear(clear) => Some(TouchedRegion::of(&clear.area)),
+            HistoryAction::SetClass(set_class) => area_at(set_class.layer).map(TouchedRegion::of),
+            HistoryAction::SetKind(set_kind) => area_at(set_kind.layer).map(TouchedRegion::of),
+            // Neither the hierarchy nor attributes are drawn
+            HistoryAction::SetParent(_) | HistoryAction::SetAttributes(_) => None,
+            HistoryAction::SetLayer(set_layer) => area_at(set_layer.layer).map(TouchedRegion::of),
+            HistoryAction::Replace(replace) => {
+                [area_at(replace.layer), replace.pixel_area.as_ref()]
+                    .into_iter()
+                    .flatten()
+                    .map(TouchedRegion::of)
+                    .reduce(TouchedRegion::union)
+            }
+            HistoryAction::Split(split) => area_at(split.layer).map(TouchedRegion::of),
+            HistoryAction::Swap(swap) => swap
+                .layers
+                .into_iter()
+                .filter_map(area_at)
+                .map(TouchedRegion::of)
+                .reduce(TouchedRegion::union),
+            HistoryAction::Merge(merge) => std::iter::once(merge.layer)
+                .chain(merge.others.iter().copied())
+                .filter_map(area_at)
+                .map(TouchedRegion::of)
+                .reduce(TouchedRegion::union),
+            HistoryAction::AdjustConfidence(adjust) => Some(TouchedRegion::of(&adjust.area)),
+        }
+    }
+
     pub fn layer(&self) -> Option<usize> {
         match self {
             HistoryAction::Add(x) => x.layer,
+            HistoryAction::Overwrite(x) => x.layer,
             HistoryAction::Reset => None,
             HistoryAction::Clear(x) => x.layer,
+            HistoryAction::SetClass(x) => Some(x.layer),
+            HistoryAction::SetKind(x) => Some(x.layer),
+            HistoryAction::SetParent(x) => Some(x.layer),
+            HistoryAction::SetAttributes(x) => Some(x.layer),
+            HistoryAction::SetLayer(x) => Some(x.layer),
+            HistoryAction::Replace(x) => Some(x.layer),
+            HistoryAction::Split(x) => Some(x.layer),
+            HistoryAction::Swap(x) => Some(x.layers[0]),
+            HistoryAction::Merge(x) => Some(x.layer),
+            HistoryAction::AdjustConfidence(_) => None,
         }
     }
     pub fn apply(&self, mut rest: Vec<Option<PixelArea>>) -> Vec<Option<PixelArea>> {
         match self {
-            HistoryAction::Add(add) => match add.layer {
-                None => {
-                    rest.push(Some(add.pixel_area.clone()));
-                    rest
-                }
-                Some(idx) => {
-                    while rest.len() <= idx {
-                        rest.push(None);
+            HistoryAction::Add(add) => add_at(rest, &add.pixel_area, add.layer),
+            HistoryAction::Overwrite(overwrite) => {
+                let ancestors = ancestors(&rest, overwrite.pixel_area.parent);
+                for (idx, slot) in rest.iter_mut().enumerate() {
+                    if Some(idx) != overwrite.layer && !ancestors.contains(&idx) {
+                        *slot = slot
+                            .take()
+                            .and_then(|area| area.cleared(&overwrite.pixel_area));
                     }
-                    rest[idx] = match rest[idx].take() {
-                        Some(existing) => {
-                            let new_iter = add.pixel_area.pixels.iter::<RangeInclusive<u64>>();
-                            let new_iter = range_set_blaze::CheckSortedDisjointMap::new(
-                                // Meta will soon be removed, this is a workaround
-                                new_iter.map(|(r, m)| (r, *m)).coalesce(|a, b| {
-                                    if *a.0.end() == b.0.start() - 1 {
-                                        Ok((*a.0.start()..=*b.0.end(), a.1))
-                                    } else {
-                                        Err((a, b))
-                                    }
-                                }),
-                            );
-                            existing.map_inplace(|existing_iter| {
-                                let r = existing_iter.union(new_iter);
-                                // Temporary fix until Meta-Removeal: Merge Meta to avoid adjacent
-                                r.coalesce(|a, b| {
-                                    if *a.0.end() == b.0.start() - 1 {
-                                        Ok((*a.0.start()..=*b.0.end(), a.1))
-                                    } else {
-                                        Err((a, b))
-                                    }
-                                })
-                            })
-                        }
-                        None => Some(add.pixel_area.clone()),
-                    };
-                    rest
                 }
-            },
+                add_at(rest, &overwrite.pixel_area, overwrite.layer)
+            }
             HistoryAction::Reset => {
-                rest.clear();
+                // Locked areas are kept at their layers
+                for slot in &mut rest {
+                    if !slot.as_ref().is_some_and(|area| area.layer.locked) {
+                        *slot = None;
+                    }
+                }
+                let len = rest
+                    .iter()
+                    .rposition(Option::is_some)
+                    .map_or(0, |last| last + 1);
+                rest.truncate(len);
                 rest
             }
             HistoryAction::Clear(clear) => match clear.layer {
                 None => rest
                     .into_iter()
-                    .map(|opt_area| {
-                        opt_area.and_then(|area| {
-                            let width = area.pixels.width();
-                            area.map_inplace(|x| {
-                                x.map_and_set_difference(
-                                    clear.ranges.iter_global_with::<RangeInclusive<u64>>(width),
-                                )
-                            })
-                        })
-                    })
+                    .map(|opt_area| opt_area.and_then(|area| area.cleared(&clear.area)))
                     .collect(),
                 Some(idx) => {
                     if let Some(opt_area) = rest.get_mut(idx) {
-                        *opt_area = opt_area.take().and_then(|area| {
-                            let width = area.pixels.width();
-                            area.map_inplace(|x| {
-                                x.map_and_set_difference(
-                                    clear.ranges.iter_global_with::<RangeInclusive<u64>>(width),
-                                )
-                            })
-                        });
+                        *opt_area = opt_area.take().and_then(|area| area.cleared(&clear.area));
                     }
                     rest
                 }
             },
+            HistoryAction::SetClass(set_class) => {
+                if let Some(Some(area)) = rest.get_mut(set_class.layer) {
+                    area.class = set_class.class;
+                }
+                rest
+            }
+            HistoryAction::SetKind(set_kind) => {
+                if let Some(Some(area)) = rest.get_mut(set_kind.layer) {
+                    area.kind = set_kind.kind;
+                }
+                rest
+            }
+            HistoryAction::SetParent(set_parent) => {
+                if let Some(Some(area)) = rest.get_mut(set_parent.layer) {
+                    area.parent = set_parent.parent;
+                }
+                rest
+            }
+            HistoryAction::SetAttributes(set_attributes) => {
+                if let Some(Some(area)) = rest.get_mut(set_attributes.layer) {
+                    area.attributes = set_attributes.attributes.clone();
+                }
+                rest
+            }
+            HistoryAction::SetLayer(set_layer) => {
+                if let Some(Some(area)) = rest.get_mut(set_layer.layer) {
+                    area.layer = set_layer.settings.clone();
+                }
+                rest
+            }
+            HistoryAction::Replace(replace) => {
+                if let Some(slot) = rest.get_mut(replace.layer) {
+                    *slot = replace.pixel_area.clone();
+                }
+                rest
+            }
+            HistoryAction::Split(split) => {
+                let mut parts = split.parts.iter().cloned();
+                if let Some(slot) = rest.get_mut(split.layer) {
+                    *slot = parts.next();
+                    rest.extend(parts.map(Some));
+                }
+                rest
+            }
+            HistoryAction::Swap(HistoryActionSwap { layers: [a, b] }) => {
+                if *a < rest.len() && *b < rest.len() {
+                    rest.swap(*a, *b);
+                    reparent(&mut rest, |layer| match layer {
+                        _ if layer == *a => *b,
+                        _ if layer == *b => *a,
+                        _ => layer,
+                    });
+                }
+                rest
+            }
+            HistoryAction::Merge(merge) => {
+                let target = rest.get(merge.layer).cloned().flatten();
+                let merged = merge
+                    .others
+                    .iter()
+                    .filter(|other| **other != merge.layer)
+                    .filter_map(|other| rest.get_mut(*other)?.take())
+                    .fold(target, |acc, other| match acc {
+                        Some(acc) => acc.union(&other),
+                        None => Some(other),
+                    });
+                if let Some(slot) = rest.get_mut(merge.layer) {
+                    *slot = merged;
+                }
+                // Parts of the merged areas become parts of the result
+                reparent(&mut rest, |layer| {
+                    if merge.others.contains(&layer) {
+                        merge.layer
+                    } else {
+                        layer
+                    }
+                });
+                rest
+            }
+            HistoryAction::AdjustConfidence(adjust) => rest
+                .into_iter()
+                .map(|opt_area| {
+                    opt_area.and_then(|area| {
+                        if area.layer.locked {
+                            Some(area)
+                        } else {
+                            area.adjust_confidence(&adjust.area, adjust.delta)
+                        }
+                    })
+                })
+                .collect(),
+        }
+    }
+}
+
+impl PixelArea {
+    /// Difference with `area`, unless this area is locked
+    fn cleared(self, area: &PixelArea) -> Option<PixelArea> {
+        if self.layer.locked {
+            Some(self)
+        } else {
+            self.difference(area)
+        }
+    }
+}
+
+/// `parent`, its parent and so on, as long as the areas exist
+pub(super) fn ancestors(areas: &[Option<PixelArea>], parent: Option<usize>) -> Vec<usize> {
+    let mut result = Vec::new();
+    let mut next = parent;
+    while let Some(layer) = next.filter(|layer| !result.contains(layer)) {
+        let Some(Some(area)) = areas.get(layer) else {
+            break;
+        };
+        result.push(layer);
+        next = area.parent;
+    }
+    result
+}
+
+/// Changes the parents of all areas by `map`. Areas which would become their own parent become
+/// top level areas.
+fn reparent(areas: &mut [Option<PixelArea>], map: impl Fn(usize) -> usize) {
+    for (layer, area) in areas.iter_mut().enumerate() {
+        if let Some(area) = area {
+            area.parent = area.parent.map(&map).filter(|parent| *parent != layer);
         }
     }
 }
 
+/// Appends `pixel_area` or adds it to the area at `layer`
+fn add_at(
+    mut rest: Vec<Option<PixelArea>>,
+    pixel_area: &PixelArea,
+    layer: Option<usize>,
+) -> Vec<Option<PixelArea>> {
+    match layer {
+        None => rest.push(Some(pixel_area.clone())),
+        Some(idx) => {
+            while rest.len() <= idx {
+                rest.push(None);
+            }
+            rest[idx] = match rest[idx].take() {
+                Some(existing) => existing.union(pixel_area),
+                None => Some(pixel_area.clone()),
+            };
+        }
+    }
+    rest
+}
+
 impl range_set_blaze::ValueRef for Meta {
     type Target = Meta;
 
@@ -143,6 +418,11 @@ impl History {
         self.actions.iter().take(self.end)
     }
 
+    /// Number of actions which are currently applied
+    pub fn position(&self) -> usize {
+        self.end
+    }
+
     pub(crate) fn random_seed(&self) -> u16 {
         self.end as u16
     }
@@ -182,6 +462,11 @@ impl History {
         Some(item)
     }
 
+    /// Action which would be applied by the next `redo`
+    pub fn peek_redo(&self) -> Option<&HistoryAction> {
+        self.actions.get(self.end)
+    }
+
     pub fn undo(&mut self) -> Option<&HistoryAction> {
         let item = self.actions.get(self.end.checked_sub(1)?)?;
         self.end -= 1;
@@ -234,4 +519,179 @@ mod tests {
         history.push(item2);
         assert_eq!(None, history.redo());
     }
+
+    #[test]
+    fn replace_touches_old_and_new_area() {
+        let old = PixelArea::single_range_total_black(1, 1, ONE, TEN);
+        let replace = HistoryAction::Replace(HistoryActionReplace {
+            layer: 0,
+            pixel_area: Some(PixelArea::single_range_total_black(5, 3, ONE, TEN)),
+        });
+        assert_eq!(
+            replace.touched_region(&[Some(old)]),
+            Some(TouchedRegion::Bounds([[1, 1], [5, 3]]))
+        );
+        let set_class = HistoryAction::SetClass(HistoryActionSetClass {
+            layer: 1,
+            class: None,
+        });
+        assert_eq!(set_class.touched_region(&[]), None);
+    }
+
+    #[test]
+    fn reset_keeps_locked_areas() {
+        let area = |x| Some(PixelArea::single_range_total_black(x, 0, ONE, TEN));
+        let locked = area(1).map(|area| {
+            area.with_layer(crate::LayerSettings {
+                locked: true,
+                ..Default::default()
+            })
+        });
+        let reset = HistoryAction::Reset.apply(vec![area(0), locked.clone(), area(2)]);
+        assert_eq!(reset, vec![None, locked]);
+        assert!(
+            HistoryAction::Reset
+                .apply(vec![area(0), area(2)])
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn swap_and_merge() {
+        let area = |x| Some(PixelArea::single_range_total_black(x, 0, ONE, TEN));
+        let apply = |action: HistoryAction| action.apply(vec![area(0), area(1), None, area(3)]);
+
+        let swapped = apply(HistoryAction::Swap(HistoryActionSwap { layers: [0, 3] }));
+        assert_eq!(swapped, vec![area(3), area(1), None, area(0)]);
+
+        let merged = apply(HistoryAction::Merge(HistoryActionMerge {
+            layer: 1,
+            others: vec![2, 3],
+        }));
+        let expected = area(1).unwrap().union(&area(3).unwrap());
+        assert_eq!(merged, vec![area(0), expected, None, None]);
+    }
+
+    #[test]
+    fn overwrite_removes_pixels_from_other_areas() {
+        let area = |x, len| {
+            Some(PixelArea::single_range_total_black(
+                x,
+                0,
+                NonZeroU32::new(len).unwrap(),
+                TEN,
+            ))
+        };
+        let overwrite = HistoryAction::Overwrite(HistoryActionOverwrite {
+            pixel_area: area(2, 4).unwrap(),
+            layer: None,
+        });
+        assert_eq!(
+            overwrite.apply(vec![area(0, 3), area(3, 2), area(5, 3)]),
+            vec![area(0, 2), None, area(6, 2), area(2, 4)]
+        );
+    }
+
+    #[test]
+    fn clear_keeps_locked_areas() {
+        let mut locked = PixelArea::single_range_total_black(0, 0, TEN, TEN);
+        locked.layer.locked = true;
+        let unlocked = PixelArea::single_range_total_black(0, 1, TEN, TEN);
+        let clear = HistoryAction::Clear(HistoryActionClear {
+            area: locked.union(&unlocked).unwrap(),
+            layer: None,
+        });
+        assert_eq!(
+            clear.apply(vec![Some(locked.clone()), Some(unlocked)]),
+            vec![Some(locked), None]
+        );
+    }
+
+    #[test]
+    fn parents_follow_swap_and_merge() {
+        let area = |y| PixelArea::single_range_total_black(0, y, TEN, TEN);
+        let areas = vec![
+            Some(area(0)),
+            Some(area(1).with_parent(Some(0))),
+            Some(area(2).with_parent(Some(1))),
+        ];
+        let swapped = HistoryAction::Swap(HistoryActionSwap { layers: [0, 1] }).apply(areas);
+        let parents = |areas: &[Option<PixelArea>]| {
+            areas
+                .iter()
+                .map(|area| area.as_ref().map(|area| area.parent))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            parents(&swapped),
+            vec![Some(Some(1)), Some(None), Some(Some(0))]
+        );
+
+        let merged = HistoryAction::Merge(HistoryActionMerge {
+            layer: 1,
+            others: vec![0],
+        })
+        .apply(swapped);
+        assert_eq!(parents(&merged), vec![None, Some(None), Some(Some(1))]);
+    }
+
+    #[test]
+    fn adjust_confidence_keeps_locked_areas() {
+        let mut locked = PixelArea::single_range_total_black(0, 0, TEN, TEN);
+        locked.layer.locked = true;
+        let unlocked = PixelArea::single_range_total_black(0, 1, TEN, TEN);
+        let adjust = HistoryAction::AdjustConfidence(HistoryActionAdjustConfidence {
+            area: locked.union(&unlocked).unwrap(),
+            delta: -55,
+        });
+        let adjusted = adjust.apply(vec![Some(locked.clone()), Some(unlocked)]);
+        assert_eq!(adjusted[0], Some(locked));
+        let confidences = adjusted[1]
+            .as_ref()
+            .unwrap()
+            .row_runs()
+            .map(|(_, _, meta)| meta.confidence())
+            .collect::<Vec<_>>();
+        assert_eq!(confidences, [200]);
+    }
+
+    #[test]
+    fn attributes_survive_clearing_pixels() {
+        let attributes = Attributes::from([
+            ("occluded".to_owned(), crate::AttributeValue::Bool(true)),
+            (
+                "comment".to_owned(),
+                crate::AttributeValue::Text("blurry".into()),
+            ),
+        ]);
+        let set = HistoryAction::SetAttributes(HistoryActionSetAttributes {
+            layer: 0,
+            attributes: attributes.clone(),
+        });
+        let clear = HistoryAction::Clear(HistoryActionClear {
+            area: PixelArea::single_range_total_black(0, 0, ONE, TEN),
+            layer: None,
+        });
+        let areas = vec![Some(PixelArea::single_range_total_black(0, 0, TEN, TEN))];
+        let cleared = clear.apply(set.apply(areas));
+        assert_eq!(cleared[0].as_ref().unwrap().attributes, attributes);
+    }
+
+    #[test]
+    fn set_class_is_undoable() {
+        let area = PixelArea::single_range_total_black(0, 0, ONE, TEN);
+        let mut history = History::default();
+        history.push(HistoryAction::SetClass(HistoryActionSetClass {
+            layer: 0,
+            class: Some(ClassId(3)),
+        }));
+        let apply = |history: &History| {
+            history
+                .iter()
+                .fold(vec![Some(area.clone())], |acc, a| a.apply(acc))
+        };
+        assert_eq!(apply(&history)[0].as_ref().unwrap().class, Some(ClassId(3)));
+        history.undo();
+        assert_eq!(apply(&history)[0].as_ref().unwrap().class, None);
+    }
 }
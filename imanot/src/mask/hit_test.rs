@@ -0,0 +1,67 @@
+//! Lookup of the topmost visible area at a pixel.
+//! Later areas are drawn above earlier ones, so the last run containing the pixel wins.
+
+use std::ops::Range;
+
+use crate::PixelArea;
+
+pub(super) struct HitIndex {
+    /// Runs of all areas by row as (x_start..x_end, layer), in layer order
+    rows: Vec<Vec<(Range<u32>, usize)>>,
+}
+
+impl HitIndex {
+    pub(super) fn new(areas: &[Option<PixelArea>], height: usize) -> Self {
+        let mut rows = vec![Vec::new(); height];
+        for (layer, area) in areas.iter().enumerate() {
+            let Some(area) = area.as_ref().filter(|area| area.layer.visible) else {
+                continue;
+            };
+            for (y, x_range, _) in area.row_runs() {
+                if let Some(row) = rows.get_mut(y as usize) {
+                    row.push((x_range.start as u32..x_range.end as u32, layer));
+                }
+            }
+        }
+        Self { rows }
+    }
+
+    /// Layer of the topmost area containing the pixel
+    pub(super) fn get(&self, [x, y]: [u32; 2]) -> Option<usize> {
+        self.rows
+            .get(y as usize)?
+            .iter()
+            .rev()
+            .find(|(x_range, _)| x_range.contains(&x))
+            .map(|(_, layer)| *layer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU32;
+
+    use super::*;
+
+    const TEN: NonZeroU32 = NonZeroU32::new(10).unwrap();
+
+    fn run(x: u32, y: u32, len: u32) -> Option<PixelArea> {
+        Some(PixelArea::single_range_total_black(
+            x,
+            y,
+            NonZeroU32::new(len).unwrap(),
+            TEN,
+        ))
+    }
+
+    #[test]
+    fn topmost_area_wins() {
+        let index = HitIndex::new(&[run(0, 1, 6), None, run(4, 1, 4), run(0, 2, 3)], 10);
+        assert_eq!(index.get([2, 1]), Some(0));
+        assert_eq!(index.get([5, 1]), Some(2));
+        assert_eq!(index.get([8, 1]), None);
+        assert_eq!(index.get([0, 2]), Some(3));
+        assert_eq!(index.get([0, 0]), None);
+        assert_eq!(index.get([0, 20]), None);
+    }
+}
@@ -0,0 +1,157 @@
+//! Cached result of applying the [`History`] to the base annotations.
+//! New actions are applied incrementally. Undo replays from the nearest checkpoint, which are
+//! taken every [`CHECKPOINT_INTERVAL`] actions, instead of from the base annotations.
+//! At most [`MAX_CHECKPOINTS`] are kept, older ones are thinned out so recent history stays dense.
+
+use crate::{History, HistoryAction, PixelArea};
+
+const CHECKPOINT_INTERVAL: usize = 32;
+/// Checkpoints besides the base annotations, each holds a copy of all areas
+const MAX_CHECKPOINTS: usize = 16;
+
+pub(super) struct MaterializedState {
+    current: Vec<Option<PixelArea>>,
+    /// Number of applied actions with the state after applying them, ordered by position.
+    /// The first checkpoint holds the base annotations and is never removed.
+    checkpoints: Vec<(usize, Vec<Option<PixelArea>>)>,
+}
+
+impl MaterializedState {
+    pub(super) fn new(base: Vec<Option<PixelArea>>, history: &History) -> Self {
+        let mut state = Self {
+            current: Vec::new(),
+            checkpoints: vec![(0, base)],
+        };
+        state.rebuild(history);
+        state
+    }
+
+    pub(super) fn base_len(&self) -> usize {
+        self.checkpoints[0].1.len()
+    }
+
+    pub(super) fn current(&self) -> &[Option<PixelArea>] {
+        &self.current
+    }
+
+    /// Applies the last action of `history`, which was just pushed
+    pub(super) fn pushed(&mut self, history: &History) {
+        let position = history.position();
+        // Checkpoints at or after `position` belong to actions, which were undone and dropped
+        self.checkpoints.retain(|(pos, _)| *pos < position);
+        if let Some(action) = history.iter().next_back() {
+            self.apply(action);
+        }
+        if position % CHECKPOINT_INTERVAL == 0 {
+            self.checkpoints.push((position, self.current.clone()));
+            self.thin_out();
+        }
+    }
+
+    /// Drops the checkpoint with the closest neighbours until at most [`MAX_CHECKPOINTS`] are left.
+    /// Ties drop the older one, so the gaps grow towards the start of the history.
+    fn thin_out(&mut self) {
+        while self.checkpoints.len() > MAX_CHECKPOINTS + 1 {
+            let idx = (1..self.checkpoints.len() - 1)
+                .min_by_key(|idx| self.checkpoints[idx + 1].0 - self.checkpoints[idx - 1].0)
+                .expect("There are more than two checkpoints");
+            self.checkpoints.remove(idx);
+        }
+    }
+
+    /// Applies an action, which was redone
+    pub(super) fn redone(&mut self, action: &HistoryAction) {
+        self.apply(action);
+    }
+
+    /// Replays `history` from the last checkpoint before its position, e.g. after undo
+    pub(super) fn rebuild(&mut self, history: &History) {
+        let position = history.position();
+        let (checkpoint_pos, snapshot) = self
+            .checkpoints
+            .iter()
+            .rev()
+            .find(|(pos, _)| *pos <= position)
+            .expect("The base checkpoint is at position 0");
+        self.current = history
+            .iter()
+            .skip(*checkpoint_pos)
+            .fold(snapshot.clone(), |acc, action| action.apply(acc));
+    }
+
+    fn apply(&mut self, action: &HistoryAction) {
+        self.current = action.apply(std::mem::take(&mut self.current));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU32;
+
+    use super::*;
+    use crate::HistoryActionAdd;
+
+    const TEN: NonZeroU32 = NonZeroU32::new(10).unwrap();
+
+    fn add(pos: u32) -> HistoryAction {
+        HistoryAction::Add(HistoryActionAdd {
+            pixel_area: PixelArea::single_range_total_black(pos, 0, NonZeroU32::MIN, TEN),
+            layer: None,
+        })
+    }
+
+    fn replayed(history: &History) -> Vec<Option<PixelArea>> {
+        history.iter().fold(Vec::new(), |acc, a| a.apply(acc))
+    }
+
+    #[test]
+    fn matches_replay_after_undo_redo_and_branching() {
+        let mut history = History::default();
+        let mut state = MaterializedState::new(Vec::new(), &history);
+        for pos in 0..100 {
+            history.push(add(pos));
+            state.pushed(&history);
+        }
+        assert_eq!(state.current(), replayed(&history));
+
+        for _ in 0..40 {
+            history.undo();
+        }
+        state.rebuild(&history);
+        assert_eq!(state.current(), replayed(&history));
+
+        let redone = history.redo().unwrap().clone();
+        state.redone(&redone);
+        assert_eq!(state.current(), replayed(&history));
+
+        // Dropping the undone actions must drop their checkpoints too
+        history.push(HistoryAction::Reset);
+        state.pushed(&history);
+        for pos in 0..10 {
+            history.push(add(pos));
+            state.pushed(&history);
+        }
+        history.undo();
+        state.rebuild(&history);
+        assert_eq!(state.current(), replayed(&history));
+    }
+
+    #[test]
+    fn checkpoints_are_limited() {
+        let mut history = History::default();
+        let mut state = MaterializedState::new(Vec::new(), &history);
+        for pos in 0..2000 {
+            history.push(add(pos % 100));
+            state.pushed(&history);
+        }
+        assert_eq!(state.checkpoints.len(), MAX_CHECKPOINTS + 1);
+        assert_eq!(state.checkpoints[0].0, 0);
+        assert_eq!(state.checkpoints.last().unwrap().0, 1984);
+
+        for _ in 0..1500 {
+            history.undo();
+        }
+        state.rebuild(&history);
+        assert_eq!(state.current(), replayed(&history));
+    }
+}
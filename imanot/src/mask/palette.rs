@@ -0,0 +1,157 @@
+//! Colors for new unlabeled areas, chosen to differ from the areas they touch.
+
+use super::random_color::random_color_from_seed;
+
+/// Number of hues [`ColorPalette::Distinct`] chooses from for every new area
+const DISTINCT_CANDIDATES: u16 = 24;
+
+/// Colors new unlabeled areas are chosen from
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ColorPalette {
+    /// Saturated colors of all hues
+    #[default]
+    Distinct,
+    /// Colorblind-safe palette by Okabe and Ito, without black
+    OkabeIto,
+    /// Colorblind-safe "bright" palette by Paul Tol
+    TolBright,
+}
+
+impl ColorPalette {
+    pub const ALL: [Self; 3] = [Self::Distinct, Self::OkabeIto, Self::TolBright];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorPalette::Distinct => "Distinct hues",
+            ColorPalette::OkabeIto => "Okabe-Ito (colorblind-safe)",
+            ColorPalette::TolBright => "Tol bright (colorblind-safe)",
+        }
+    }
+
+    /// Colors to choose from, starting at a position depending on `seed`
+    fn candidates(self, seed: u16) -> Vec<[u8; 3]> {
+        let fixed: &[[u8; 3]] = match self {
+            ColorPalette::Distinct => {
+                return (0..DISTINCT_CANDIDATES)
+                    .map(|i| random_color_from_seed(seed.wrapping_add(i)))
+                    .collect();
+            }
+            ColorPalette::OkabeIto => &[
+                [230, 159, 0],
+                [86, 180, 233],
+                [0, 158, 115],
+                [240, 228, 66],
+                [0, 114, 178],
+                [213, 94, 0],
+                [204, 121, 167],
+            ],
+            ColorPalette::TolBright => &[
+                [68, 119, 170],
+                [102, 204, 238],
+                [34, 136, 51],
+                [204, 187, 68],
+                [238, 102, 119],
+                [170, 51, 119],
+                [187, 187, 187],
+            ],
+        };
+        let start = seed as usize % fixed.len();
+        fixed[start..]
+            .iter()
+            .chain(&fixed[..start])
+            .copied()
+            .collect()
+    }
+
+    /// Candidate with the largest perceptual distance to all `neighbors`. Ties are broken by
+    /// preferring colors less often in `used`, then by the order of the candidates.
+    pub fn pick(self, seed: u16, neighbors: &[[u8; 3]], used: &[[u8; 3]]) -> [u8; 3] {
+        let neighbors = neighbors.iter().map(|c| oklab(*c)).collect::<Vec<_>>();
+        let score = |color: [u8; 3]| {
+            let lab = oklab(color);
+            let distance = neighbors
+                .iter()
+                .map(|other| {
+                    lab.iter()
+                        .zip(other)
+                        .map(|(a, b)| (a - b) * (a - b))
+                        .sum::<f32>()
+                })
+                .fold(f32::INFINITY, f32::min);
+            let uses = used.iter().filter(|c| **c == color).count();
+            (distance, uses)
+        };
+        let mut best: Option<([u8; 3], (f32, usize))> = None;
+        for color in self.candidates(seed) {
+            let (distance, uses) = score(color);
+            let better = match best {
+                None => true,
+                Some((_, (best_distance, best_uses))) => {
+                    distance > best_distance || (distance == best_distance && uses < best_uses)
+                }
+            };
+            if better {
+                best = Some((color, (distance, uses)));
+            }
+        }
+        best.map_or_else(|| random_color_from_seed(seed), |(color, _)| color)
+    }
+}
+
+/// sRGB to the Oklab color space, where euclidean distances match perceived differences
+fn oklab(rgb: [u8; 3]) -> [f32; 3] {
+    let [r, g, b] = rgb.map(|c| {
+        let c = c as f32 / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    });
+    let l = (0.412_221_46 * r + 0.536_332_55 * g + 0.051_445_995 * b).cbrt();
+    let m = (0.211_903_5 * r + 0.680_699_5 * g + 0.107_396_96 * b).cbrt();
+    let s = (0.088_302_46 * r + 0.281_718_85 * g + 0.629_978_7 * b).cbrt();
+    [
+        0.210_454_26 * l + 0.793_617_8 * m - 0.004_072_047 * s,
+        1.977_998_5 * l - 2.428_592_2 * m + 0.450_593_7 * s,
+        0.025_904_037 * l + 0.782_771_77 * m - 0.808_675_77 * s,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeds_beyond_256_get_new_colors() {
+        assert_ne!(random_color_from_seed(3), random_color_from_seed(259));
+    }
+
+    #[test]
+    fn pick_avoids_neighbor_colors() {
+        for palette in ColorPalette::ALL {
+            let first = palette.pick(0, &[], &[]);
+            let second = palette.pick(0, &[first], &[first]);
+            assert_ne!(first, second);
+            let distance = |a, b| {
+                let (a, b): ([f32; 3], [f32; 3]) = (oklab(a), oklab(b));
+                a.iter().zip(b).map(|(a, b)| (a - b).powi(2)).sum::<f32>()
+            };
+            assert!(
+                palette
+                    .candidates(0)
+                    .into_iter()
+                    .all(|c| distance(c, first) <= distance(second, first))
+            );
+        }
+    }
+
+    #[test]
+    fn pick_prefers_unused_colors() {
+        let palette = ColorPalette::OkabeIto;
+        let first = palette.pick(0, &[], &[]);
+        assert_ne!(palette.pick(0, &[], &[first]), first);
+    }
+}
@@ -1,13 +1,10 @@
-/// Generate a random color from a seed using HSV color space
+/// Generate a random color from a seed using HSV color space.
+/// Hues of consecutive seeds are spread by the golden ratio, so they differ strongly and only
+/// repeat after all 65536 seeds.
 pub fn random_color_from_seed(seed: u16) -> [u8; 3] {
     fn pseudo_random_permutation(seed: u16) -> f32 {
-        let mut num = (seed & 0xFF) as u8;
-
-        for _ in 0..2 {
-            num = num.wrapping_mul(197).rotate_left(5) ^ 0x5A;
-        }
-
-        num as f32 / (u8::MAX as f32)
+        const GOLDEN_RATIO_CONJUGATE: f64 = 0.618_033_988_749_895;
+        (seed as f64 * GOLDEN_RATIO_CONJUGATE).fract() as f32
     }
 
     fn hsv_to_rgb(h: f32, s: f32, v: f32) -> [u8; 3] {
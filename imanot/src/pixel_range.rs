@@ -1,7 +1,18 @@
-use std::num::{NonZero, NonZeroU32, NonZeroU64};
-use std::ops::RangeInclusive;
+use std::num::{NonZeroU32, NonZeroU64};
+use std::ops::{Range, RangeInclusive};
 
-use imask::{ImageDimension, ImaskSet, NonZeroRange, SortedRangesMap, SourceIteratorMap};
+use imask::{ImageDimension, ImaskSet, NonZeroRange, Rect, SortedRangesMap, SourceIteratorMap};
+
+use crate::{Attributes, ClassId, LayerSettings};
+
+mod components;
+mod geometry;
+mod morphology;
+mod set_ops;
+
+pub use components::*;
+pub use geometry::*;
+pub use morphology::*;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 #[non_exhaustive]
@@ -41,24 +52,116 @@ impl CreateTotal for MetaRange {
 
 type MetaRanges = SortedRangesMap<u32, u32, Vec<Meta>>;
 
+/// Rows are never wider than this, so `y * ROW_STRIDE + x` orders pixels row by row without
+/// knowing the image width and runs of different rows never touch
+pub(crate) const ROW_STRIDE: u64 = 1 << 32;
+
+/// Pixels are stored relative to a tight region of interest, which is `pixels.bounds()`.
+/// Use [`PixelArea::row_runs`] or [`PixelArea::global_ranges`] for image coordinates.
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[non_exhaustive]
 pub struct PixelArea {
     pub pixels: MetaRanges,
+    /// Color of unlabeled areas. Labeled areas are drawn with the color of their class.
     pub color: [u8; 3],
+    pub class: Option<ClassId>,
+    pub layer: LayerSettings,
+    pub kind: AreaKind,
+    /// Layer of the area this area is a part of, e.g. the car of a wheel
+    pub parent: Option<usize>,
+    pub attributes: Attributes,
+}
+
+/// Meaning of an area for training
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum AreaKind {
+    #[default]
+    Object,
+    /// Neither background nor object, e.g. ambiguous or crowded regions. Excluded from the loss.
+    Ignore,
 }
 
 impl PixelArea {
+    /// `pixels` are global positions in an image as wide as their bounds
     pub fn new(
         pixels: impl IntoIterator<Item = MetaRange, IntoIter: ImageDimension>,
         color: [u8; 3],
     ) -> Option<Self> {
+        let iter = pixels.into_iter();
+        let image_width = iter.bounds().width.get() as u64;
+        Self::from_row_runs(
+            iter.flat_map(move |r| {
+                split_rows(r.range.start..r.range.end, image_width)
+                    .map(move |(y, x_range)| (y, x_range, r.meta))
+            }),
+            color,
+        )
+    }
+
+    /// `pixels` are positions relative to `roi`, which doesn't have to be tight
+    pub fn from_roi(
+        pixels: impl IntoIterator<Item = MetaRange>,
+        roi: Rect<u32>,
+        color: [u8; 3],
+    ) -> Option<Self> {
+        let (roi_x, roi_y) = (roi.x as u64, roi.y as u64);
+        let roi_width = roi.width.get() as u64;
+        Self::from_row_runs(
+            pixels.into_iter().flat_map(move |r| {
+                split_rows(r.range.start..r.range.end, roi_width).map(move |(y, x_range)| {
+                    (
+                        roi_y + y,
+                        roi_x + x_range.start..roi_x + x_range.end,
+                        r.meta,
+                    )
+                })
+            }),
+            color,
+        )
+    }
+
+    /// Creates an area from non-empty runs as (y, x_start..x_end, meta), ordered by position
+    pub(crate) fn from_row_runs(
+        runs: impl IntoIterator<Item = (u64, Range<u64>, Meta)>,
+        color: [u8; 3],
+    ) -> Option<Self> {
+        let runs = runs.into_iter().collect::<Vec<_>>();
+        let (first_y, last_y) = (runs.first()?.0, runs.last()?.0);
+        let min_x = runs.iter().map(|(_, x_range, _)| x_range.start).min()?;
+        let max_x = runs.iter().map(|(_, x_range, _)| x_range.end).max()?;
+        let roi_width = max_x - min_x;
+
+        let mut local: Vec<(Range<u64>, Meta)> = Vec::with_capacity(runs.len());
+        for (y, x_range, meta) in runs {
+            let start = (y - first_y) * roi_width + x_range.start - min_x;
+            let end = start + x_range.end - x_range.start;
+            match local.last_mut() {
+                Some((last, last_meta)) if last.end == start && *last_meta == meta => {
+                    last.end = end
+                }
+                _ => local.push((start..end, meta)),
+            }
+        }
+        let roi = Rect::new(
+            min_x as u32,
+            first_y as u32,
+            NonZeroU32::new(roi_width as u32)?,
+            NonZeroU32::new((last_y - first_y + 1) as u32)?,
+        );
         Some(Self {
-            pixels: Self::try_from_iter(pixels)?,
+            pixels: MetaRanges::try_from_ordered_iter(local.into_iter().with_roi(roi)).ok()?,
             color,
+            class: None,
+            layer: LayerSettings::default(),
+            kind: AreaKind::default(),
+            parent: None,
+            attributes: Attributes::new(),
         })
     }
 
+    /// `f` works on the positions relative to the region of interest
     pub fn map_inplace<TIter, TFun>(self, f: TFun) -> Option<Self>
     where
         TIter: Iterator<Item = (RangeInclusive<u64>, Meta)>,
@@ -67,18 +170,21 @@ impl PixelArea {
         Some(Self {
             pixels: self.pixels.map_inplace(f)?,
             color: self.color,
+            class: self.class,
+            layer: self.layer,
+            kind: self.kind,
+            parent: self.parent,
+            attributes: self.attributes,
         })
     }
 
     pub fn with_black_color(
         pixels: impl IntoIterator<Item = MetaRange, IntoIter: ImageDimension>,
     ) -> Option<Self> {
-        Some(Self {
-            pixels: Self::try_from_iter(pixels)?,
-            color: [0, 0, 0],
-        })
+        Self::new(pixels, [0, 0, 0])
     }
 
+    /// `len` pixels starting at (x, y), continuing on the next rows if they exceed `image_width`
     pub fn single_pixel_total_color(
         x: u32,
         y: u32,
@@ -86,42 +192,223 @@ impl PixelArea {
         color: [u8; 3],
         image_width: NonZeroU32,
     ) -> Self {
-        // Very ugly, but the project currently depends on all Masks having width == ImageWidth
-
-        use imask::Rect;
-        let start = x + y * image_width.get();
-        let height = NonZero::new(y + 1).expect("Cannot be zero without overflow");
-        Self {
-            pixels: MetaRanges::new(
-                NonZeroRange::from_span(start, len),
-                Meta::default(),
-                Rect::new(0, 0, image_width, height),
-            ),
+        let start = x as u64 + y as u64 * image_width.get() as u64;
+        Self::from_row_runs(
+            split_rows(start..start + len.get() as u64, image_width.get() as u64)
+                .map(|(y, x_range)| (y, x_range, Meta::default())),
             color,
-        }
+        )
+        .expect("len is not zero")
     }
     #[cfg(test)]
     pub fn single_range_total_black(x: u32, y: u32, len: NonZeroU32, width: NonZeroU32) -> Self {
         Self::single_pixel_total_color(x, y, len, [0, 0, 0], width)
     }
 
-    fn try_from_iter(
-        pixels: impl IntoIterator<Item = MetaRange, IntoIter: ImageDimension>,
-    ) -> Option<MetaRanges> {
-        let iter = pixels.into_iter();
-        let roi = iter.bounds();
-        MetaRanges::try_from_ordered_iter(
-            iter.map(|r| (r.range.start..r.range.end, r.meta))
-                .with_roi(roi),
-        )
-        .ok()
+    /// `pixels` must already be relative to their bounds
+    pub fn from_ranges(pixels: MetaRanges, color: [u8; 3]) -> Self {
+        Self {
+            pixels,
+            color,
+            class: None,
+            layer: LayerSettings::default(),
+            kind: AreaKind::default(),
+            parent: None,
+            attributes: Attributes::new(),
+        }
     }
 
-    pub fn from_ranges(pixels: MetaRanges, color: [u8; 3]) -> Self {
-        Self { pixels, color }
+    pub fn with_class(mut self, class: Option<ClassId>) -> Self {
+        self.class = class;
+        self
+    }
+
+    pub fn with_layer(mut self, layer: LayerSettings) -> Self {
+        self.layer = layer;
+        self
+    }
+
+    pub fn with_kind(mut self, kind: AreaKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    pub fn with_parent(mut self, parent: Option<usize>) -> Self {
+        self.parent = parent;
+        self
+    }
+
+    pub fn with_attributes(mut self, attributes: Attributes) -> Self {
+        self.attributes = attributes;
+        self
+    }
+
+    /// Takes the class, the layer settings, the kind, the parent and the attributes of `other`,
+    /// e.g. for a part of it
+    pub(crate) fn with_properties_of(mut self, other: &PixelArea) -> Self {
+        self.class = other.class;
+        self.layer = other.layer.clone();
+        self.kind = other.kind;
+        self.parent = other.parent;
+        self.attributes = other.attributes.clone();
+        self
     }
 
     pub fn range_len(&self) -> usize {
         self.pixels.len()
     }
+
+    /// Confidence averaged over all pixels in 0.0..=1.0
+    pub fn mean_confidence(&self) -> f32 {
+        let (sum, count) =
+            self.pixels
+                .iter::<Range<u64>>()
+                .fold((0, 0), |(sum, count), (range, meta)| {
+                    let len = range.end - range.start;
+                    (sum + len * meta.confidence() as u64, count + len)
+                });
+        sum as f32 / (count.max(1) * 255) as f32
+    }
+
+    /// Keeps the pixels with at least `min_confidence` and makes them fully confident
+    pub fn binarize(&self, min_confidence: u8) -> Option<PixelArea> {
+        PixelArea::from_row_runs(
+            self.row_runs()
+                .filter(|(_, _, meta)| meta.confidence() >= min_confidence)
+                .map(|(y, x_range, _)| (y, x_range, Meta::default())),
+            self.color,
+        )
+        .map(|area| area.with_properties_of(self))
+    }
+
+    /// Tight bounding rectangle in image coordinates
+    pub fn roi(&self) -> Rect<u32> {
+        let bounds = self.pixels.bounds();
+        Rect::new(bounds.x, bounds.y, bounds.width, bounds.height)
+    }
+
+    /// Runs in image coordinates split at row boundaries as (y, x_start..x_end, meta)
+    pub fn row_runs(&self) -> impl Iterator<Item = (u64, Range<u64>, Meta)> + '_ {
+        let roi = self.roi();
+        let (roi_x, roi_y) = (roi.x as u64, roi.y as u64);
+        let roi_width = roi.width.get() as u64;
+        self.pixels
+            .iter::<Range<u64>>()
+            .flat_map(move |(range, meta)| {
+                let meta = *meta;
+                split_rows(range, roi_width).map(move |(y, x_range)| {
+                    (roi_y + y, roi_x + x_range.start..roi_x + x_range.end, meta)
+                })
+            })
+    }
+
+    /// Half open positions `y * image_width + x` with their meta, one range per row run
+    pub fn global_ranges(
+        &self,
+        image_width: NonZeroU32,
+    ) -> impl Iterator<Item = (Range<u64>, Meta)> + '_ {
+        let image_width = image_width.get() as u64;
+        self.row_runs().map(move |(y, x_range, meta)| {
+            let row_start = y * image_width;
+            (row_start + x_range.start..row_start + x_range.end, meta)
+        })
+    }
+}
+
+/// Splits a range of positions in rows of `width` into (y, x_start..x_end)
+fn split_rows(range: Range<u64>, width: u64) -> impl Iterator<Item = (u64, Range<u64>)> {
+    let first_row = range.start / width;
+    let last_row = (range.end - 1) / width;
+    (first_row..=last_row).map(move |y| {
+        let row_start = y * width;
+        let start = range.start.max(row_start) - row_start;
+        let end = range.end.min(row_start + width) - row_start;
+        (y, start..end)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH_1000: NonZeroU32 = NonZeroU32::new(1000).unwrap();
+
+    fn roi_of(area: &PixelArea) -> [u32; 4] {
+        let roi = area.roi();
+        [roi.x, roi.y, roi.width.get(), roi.height.get()]
+    }
+
+    #[test]
+    fn roi_is_tight() {
+        let area =
+            PixelArea::single_range_total_black(3, 5, NonZeroU32::new(2).unwrap(), WIDTH_1000);
+        assert_eq!(roi_of(&area), [3, 5, 2, 1]);
+        assert_eq!(area.range_len(), 1);
+        assert_eq!(
+            area.row_runs().collect::<Vec<_>>(),
+            vec![(5, 3..5, Meta::default())]
+        );
+        assert_eq!(
+            area.global_ranges(WIDTH_1000).collect::<Vec<_>>(),
+            vec![(5003..5005, Meta::default())]
+        );
+    }
+
+    #[test]
+    fn ranges_wrapping_rows_are_split() {
+        let area =
+            PixelArea::single_range_total_black(998, 0, NonZeroU32::new(4).unwrap(), WIDTH_1000);
+        assert_eq!(roi_of(&area), [0, 0, 1000, 2]);
+        assert_eq!(
+            area.row_runs().map(|(y, x, _)| (y, x)).collect::<Vec<_>>(),
+            vec![(0, 998..1000), (1, 0..2)]
+        );
+    }
+
+    #[test]
+    fn binarize_merges_kept_pixels() {
+        let soft = PixelArea::new(
+            [(0, 2, 100), (2, 3, 200), (5, 1, 255)]
+                .map(|(start, len, confidence)| MetaRange {
+                    range: NonZeroRange::from_span(start, NonZeroU64::new(len).unwrap()),
+                    meta: Meta::new(confidence),
+                })
+                .into_iter()
+                .with_bounds(WIDTH_1000, WIDTH_1000),
+            [0, 0, 0],
+        )
+        .unwrap();
+        assert_eq!(
+            soft.binarize(150),
+            Some(PixelArea::single_range_total_black(
+                2,
+                0,
+                NonZeroU32::new(4).unwrap(),
+                WIDTH_1000
+            ))
+        );
+        assert_eq!(soft.binarize(0).map(|area| area.pixel_count()), Some(6));
+        assert!((soft.mean_confidence() - 1055.0 / 1530.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn from_loose_roi_equals_tight() {
+        let loose = Rect::new(
+            10,
+            20,
+            NonZeroU32::new(8).unwrap(),
+            NonZeroU32::new(8).unwrap(),
+        );
+        // (12, 21) and (13, 21) relative to the loose roi
+        let area = PixelArea::from_roi(
+            [MetaRange::new_total(10, NonZeroU64::new(2).unwrap())],
+            loose,
+            [0, 0, 0],
+        )
+        .unwrap();
+        assert_eq!(
+            area,
+            PixelArea::single_range_total_black(12, 21, NonZeroU32::new(2).unwrap(), WIDTH_1000)
+        );
+    }
 }
@@ -0,0 +1,225 @@
+//! Connected components of a [`PixelArea`], computed by joining overlapping row runs of
+//! neighbouring rows with a union-find.
+
+use std::{
+    num::{NonZeroU32, NonZeroU64},
+    ops::Range,
+};
+
+use imask::NonZeroRange;
+
+use super::{ROW_STRIDE, morphology::image_rect};
+use crate::{Meta, MetaRange, PixelArea};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub enum Connectivity {
+    /// Pixels sharing an edge are connected
+    Four,
+    /// Pixels sharing an edge or a corner are connected
+    #[default]
+    Eight,
+}
+
+impl Connectivity {
+    fn touches(self, a: &Range<u64>, b: &Range<u64>) -> bool {
+        match self {
+            Connectivity::Four => a.start < b.end && b.start < a.end,
+            Connectivity::Eight => a.start <= b.end && b.start <= a.end,
+        }
+    }
+}
+
+impl PixelArea {
+    /// Number of pixels covered by the area
+    pub fn pixel_count(&self) -> u64 {
+        self.pixels
+            .iter::<Range<u64>>()
+            .map(|(range, _)| range.end - range.start)
+            .sum()
+    }
+
+    /// Position [x, y] of the first pixel in reading order
+    pub fn first_pixel(&self) -> [u32; 2] {
+        let (y, x_range, _) = self.row_runs().next().expect("Areas are not empty");
+        [x_range.start as u32, y as u32]
+    }
+
+    /// Splits the area into its islands, ordered by their first pixel.
+    /// All components keep `color` and the other properties of `self`, like class and layer.
+    pub fn connected_components(&self, connectivity: Connectivity) -> Vec<PixelArea> {
+        let runs = self.row_runs().collect::<Vec<_>>();
+        let mut parents = (0..runs.len()).collect::<Vec<_>>();
+
+        let mut previous_row = 0..0;
+        let mut row_start = 0;
+        for idx in 0..runs.len() {
+            if idx > 0 && runs[idx].0 != runs[idx - 1].0 {
+                previous_row = if runs[idx - 1].0 + 1 == runs[idx].0 {
+                    row_start..idx
+                } else {
+                    idx..idx
+                };
+                row_start = idx;
+            }
+            for neighbour in previous_row.clone() {
+                if connectivity.touches(&runs[neighbour].1, &runs[idx].1) {
+                    let (a, b) = (find(&mut parents, neighbour), find(&mut parents, idx));
+                    parents[a.max(b)] = a.min(b);
+                }
+            }
+        }
+
+        // Roots are the smallest index of their component, so components are ordered
+        let mut component_of_root = vec![usize::MAX; runs.len()];
+        let mut components: Vec<Vec<(Range<u64>, Meta)>> = Vec::new();
+        for (idx, (y, range, meta)) in runs.into_iter().enumerate() {
+            let root = find(&mut parents, idx);
+            if component_of_root[root] == usize::MAX {
+                component_of_root[root] = components.len();
+                components.push(Vec::new());
+            }
+            let row_start = y * ROW_STRIDE;
+            components[component_of_root[root]]
+                .push((row_start + range.start..row_start + range.end, meta));
+        }
+        components
+            .into_iter()
+            .filter_map(|runs| self.with_runs(merge_adjacent(runs)))
+            .collect()
+    }
+
+    /// Adds all background regions, which are not connected to the image border.
+    /// `image_size` is [width, height].
+    pub fn fill_holes(&self, image_size: [NonZeroU32; 2]) -> PixelArea {
+        let [width, height] = image_size.map(|x| x.get() as u64);
+        let last_row = height - 1;
+        let Some(background) = self.invert(image_rect(image_size)) else {
+            return self.clone();
+        };
+        background
+            .connected_components(Connectivity::Four)
+            .into_iter()
+            .filter(|hole| {
+                !hole.row_runs().any(|(y, range, _)| {
+                    y == 0 || y == last_row || range.start == 0 || range.end == width
+                })
+            })
+            .fold(self.clone(), |area, hole| {
+                area.union(&hole).expect("Union with non-empty area")
+            })
+    }
+
+    /// Removes islands with less than `min_area` pixels. `None` if no island remains.
+    pub fn remove_small_components(
+        &self,
+        min_area: u64,
+        connectivity: Connectivity,
+    ) -> Option<PixelArea> {
+        let mut runs = self
+            .connected_components(connectivity)
+            .into_iter()
+            .filter(|component| component.pixel_count() >= min_area)
+            .flat_map(|component| component.runs())
+            .collect::<Vec<_>>();
+        runs.sort_unstable_by_key(|(range, _)| range.start);
+        self.with_runs(merge_adjacent(runs))
+    }
+}
+
+fn find(parents: &mut [usize], mut idx: usize) -> usize {
+    while parents[idx] != idx {
+        parents[idx] = parents[parents[idx]];
+        idx = parents[idx];
+    }
+    idx
+}
+
+/// Joins sorted, disjoint runs which touch and have the same meta
+fn merge_adjacent(runs: Vec<(Range<u64>, Meta)>) -> Vec<MetaRange> {
+    let mut merged: Vec<(Range<u64>, Meta)> = Vec::with_capacity(runs.len());
+    for (range, meta) in runs {
+        match merged.last_mut() {
+            Some((last, last_meta)) if last.end == range.start && *last_meta == meta => {
+                last.end = range.end
+            }
+            _ => merged.push((range, meta)),
+        }
+    }
+    merged
+        .into_iter()
+        .map(|(range, meta)| MetaRange {
+            range: NonZeroRange::from_span(
+                range.start,
+                NonZeroU64::new(range.end - range.start).expect("Runs are not empty"),
+            ),
+            meta,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use imask::ImaskSet;
+
+    use super::*;
+
+    const WIDTH_5: NonZeroU32 = NonZeroU32::new(5).unwrap();
+    const IMAGE_5: [NonZeroU32; 2] = [WIDTH_5, WIDTH_5];
+
+    /// Area of width 5 from a picture with one row per string, `#` marks a pixel
+    fn area(rows: &[&str]) -> PixelArea {
+        let ranges = rows
+            .iter()
+            .enumerate()
+            .flat_map(|(y, row)| {
+                row.char_indices()
+                    .filter(|(_, c)| *c == '#')
+                    .map(move |(x, _)| (y * 5 + x) as u64)
+            })
+            .map(|pos| (pos..pos + 1, Meta::default()))
+            .collect();
+        PixelArea::with_black_color(
+            merge_adjacent(ranges)
+                .with_bounds(WIDTH_5, NonZeroU32::new(rows.len() as u32).unwrap()),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn diagonal_pixels_depend_on_connectivity() {
+        let diagonal = area(&["#....", ".#...", "....#"]);
+        assert_eq!(diagonal.connected_components(Connectivity::Four).len(), 3);
+        let eight = diagonal.connected_components(Connectivity::Eight);
+        assert_eq!(eight.len(), 2);
+        assert_eq!(eight[0].pixel_count(), 2);
+        assert_eq!(eight[1].pixel_count(), 1);
+    }
+
+    #[test]
+    fn u_shape_is_one_component() {
+        let u = area(&["#...#", "#...#", "#####"]);
+        let components = u.connected_components(Connectivity::Four);
+        assert_eq!(components.len(), 1);
+        assert_eq!(components[0].pixel_count(), 9);
+    }
+
+    #[test]
+    fn fill_holes_ignores_open_regions() {
+        let ring = area(&[".....", ".###.", ".#.#.", ".###.", "#...."]);
+        let filled = ring.fill_holes(IMAGE_5);
+        assert_eq!(filled.pixel_count(), 10);
+        let u = area(&[".#.#.", ".#.#.", ".###."]);
+        assert_eq!(u.fill_holes(IMAGE_5).pixel_count(), 7);
+    }
+
+    #[test]
+    fn remove_small_components() {
+        let noisy = area(&["##..#", "##...", "....."]);
+        let cleaned = noisy
+            .remove_small_components(2, Connectivity::Eight)
+            .unwrap();
+        assert_eq!(cleaned.pixel_count(), 4);
+        assert_eq!(noisy.remove_small_components(5, Connectivity::Eight), None);
+    }
+}
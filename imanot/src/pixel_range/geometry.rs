@@ -0,0 +1,443 @@
+//! Shape descriptors computed from the row runs of a [`PixelArea`].
+//! Pixel (x, y) covers the unit square from corner (x, y) to (x + 1, y + 1).
+
+use crate::PixelArea;
+
+/// Smallest rectangle containing all pixels, bounds are inclusive
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct BoundingBox {
+    pub min_x: u32,
+    pub min_y: u32,
+    pub max_x: u32,
+    pub max_y: u32,
+}
+
+impl BoundingBox {
+    pub fn width(&self) -> u32 {
+        self.max_x - self.min_x + 1
+    }
+
+    pub fn height(&self) -> u32 {
+        self.max_y - self.min_y + 1
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct MaskGeometry {
+    /// Number of pixels
+    pub area: u64,
+    pub bounding_box: BoundingBox,
+    /// Mean of the pixel centers
+    pub centroid: [f64; 2],
+    /// Number of pixel edges between the area and the background (4-neighbourhood)
+    pub perimeter: u64,
+    /// Pixel corners of the convex hull, clockwise on screen (y pointing down)
+    pub convex_hull: Vec<[u32; 2]>,
+    /// 0 for circles, approaching 1 for elongated shapes
+    pub eccentricity: f64,
+    /// Angle of the major axis to the x-axis in radians, in the range -π/2..=π/2
+    pub orientation: f64,
+}
+
+impl MaskGeometry {
+    pub fn ui(&self, ui: &mut egui::Ui) {
+        let BoundingBox {
+            min_x,
+            min_y,
+            max_x,
+            max_y,
+        } = self.bounding_box;
+        ui.label(format!(
+            "area: {}, perimeter: {}\nbbox: ({min_x}, {min_y})..=({max_x}, {max_y})\ncentroid: ({:.1}, {:.1})\neccentricity: {:.2}, orientation: {:.1}°",
+            self.area,
+            self.perimeter,
+            self.centroid[0],
+            self.centroid[1],
+            self.eccentricity,
+            self.orientation.to_degrees(),
+        ));
+    }
+}
+
+impl PixelArea {
+    pub fn geometry(&self) -> MaskGeometry {
+        let runs = self.row_runs().collect::<Vec<_>>();
+        let (mut area, mut sum_x, mut sum_y) = (0u64, 0f64, 0f64);
+        let (mut sum_xx, mut sum_yy, mut sum_xy) = (0f64, 0f64, 0f64);
+        let (mut min_x, mut max_x) = (u64::MAX, 0);
+        for (y, range, _) in &runs {
+            let n = range.end - range.start;
+            let (y, n_f) = (*y as f64, n as f64);
+            // Closed forms of Σx and Σx² over the run
+            let run_sum_x = n_f * (range.start + range.end - 1) as f64 / 2.0;
+            let run_sum_xx = sum_of_squares(range.end) - sum_of_squares(range.start);
+            area += n;
+            sum_x += run_sum_x;
+            sum_y += n_f * y;
+            sum_xx += run_sum_xx;
+            sum_yy += n_f * y * y;
+            sum_xy += run_sum_x * y;
+            min_x = min_x.min(range.start);
+            max_x = max_x.max(range.end - 1);
+        }
+        let area_f = area.max(1) as f64;
+        let centroid = [sum_x / area_f, sum_y / area_f];
+        // Central second moments. 1/12 accounts for the extent of a pixel, so single
+        // rows and columns don't get a degenerate covariance.
+        let mu20 = sum_xx / area_f - centroid[0].powi(2) + 1.0 / 12.0;
+        let mu02 = sum_yy / area_f - centroid[1].powi(2) + 1.0 / 12.0;
+        let mu11 = sum_xy / area_f - centroid[0] * centroid[1];
+        let common = ((mu20 - mu02).powi(2) + 4.0 * mu11 * mu11).sqrt();
+        let (major, minor) = ((mu20 + mu02 + common) / 2.0, (mu20 + mu02 - common) / 2.0);
+
+        MaskGeometry {
+            area,
+            bounding_box: BoundingBox {
+                min_x: min_x as u32,
+                min_y: runs.first().map_or(0, |(y, _, _)| *y as u32),
+                max_x: max_x as u32,
+                max_y: runs.last().map_or(0, |(y, _, _)| *y as u32),
+            },
+            centroid,
+            perimeter: perimeter(&runs),
+            convex_hull: convex_hull(&runs),
+            eccentricity: (1.0 - minor / major).max(0.0).sqrt(),
+            orientation: 0.5 * (2.0 * mu11).atan2(mu20 - mu02),
+        }
+    }
+}
+
+impl PixelArea {
+    /// Boundary between the area and the background as segments from pixel corner to pixel
+    /// corner. Collinear neighbouring edges are joined, the confidence is ignored.
+    pub fn outline(&self) -> Vec<[[u32; 2]; 2]> {
+        let mut rows: Vec<(u64, Vec<std::ops::Range<u64>>)> = Vec::new();
+        for (y, x_range, _) in self.row_runs() {
+            match rows.last_mut() {
+                Some((row_y, ranges)) if *row_y == y => match ranges.last_mut() {
+                    Some(last) if last.end == x_range.start => last.end = x_range.end,
+                    _ => ranges.push(x_range),
+                },
+                _ => rows.push((y, vec![x_range])),
+            }
+        }
+
+        let mut segments = Vec::new();
+        let mut horizontal = |y: u64, xs: &[u64]| {
+            for pair in xs.chunks_exact(2) {
+                segments.push([[pair[0] as u32, y as u32], [pair[1] as u32, y as u32]]);
+            }
+        };
+        let mut vertical = Vec::new();
+        for (idx, (y, ranges)) in rows.iter().enumerate() {
+            let above = match idx.checked_sub(1).map(|i| &rows[i]) {
+                Some((above_y, above)) if above_y + 1 == *y => above.as_slice(),
+                _ => &[],
+            };
+            // Edges on the line between two rows are where exactly one of them is covered
+            horizontal(*y, &toggle_points(above.iter().chain(ranges)));
+            if rows
+                .get(idx + 1)
+                .is_none_or(|(below_y, _)| *below_y != y + 1)
+            {
+                horizontal(y + 1, &toggle_points(ranges.iter()));
+            }
+            vertical.extend(ranges.iter().flat_map(|r| [(r.start, *y), (r.end, *y)]));
+        }
+
+        vertical.sort_unstable();
+        let mut joined: Vec<(u64, std::ops::Range<u64>)> = Vec::new();
+        for (x, y) in vertical {
+            match joined.last_mut() {
+                Some((last_x, ys)) if *last_x == x && ys.end == y => ys.end = y + 1,
+                _ => joined.push((x, y..y + 1)),
+            }
+        }
+        segments.extend(
+            joined
+                .into_iter()
+                .map(|(x, ys)| [[x as u32, ys.start as u32], [x as u32, ys.end as u32]]),
+        );
+        segments
+    }
+
+    /// Splits the area along the infinite line through `start` and `end` in image coordinates.
+    /// Pixels are assigned by the side of their center. The first part is right of the line,
+    /// looking from `start` to `end` with y pointing down. `None` if one side would be empty.
+    pub fn split_by_line(&self, [start, end]: [[f32; 2]; 2]) -> Option<[PixelArea; 2]> {
+        let dx = (end[0] - start[0]) as f64;
+        let dy = (end[1] - start[1]) as f64;
+        if dx == 0.0 && dy == 0.0 {
+            return None;
+        }
+        let mut sides = [Vec::new(), Vec::new()];
+        for (y, x_range, meta) in self.row_runs() {
+            // Cross product of the direction and the pixel center relative to `start`, which is
+            // `at_zero - dy * x` for the pixel at x. Side 0 is where it is not negative.
+            let center_y = y as f64 + 0.5 - start[1] as f64;
+            let at_zero = dx * center_y - dy * (0.5 - start[0] as f64);
+            let (split, lower_side) = if dy > 0.0 {
+                ((at_zero / dy).floor() + 1.0, 0)
+            } else if dy < 0.0 {
+                ((at_zero / dy).ceil(), 1)
+            } else {
+                (f64::INFINITY, usize::from(at_zero < 0.0))
+            };
+            let split = (split.max(x_range.start as f64) as u64).min(x_range.end);
+            if x_range.start < split {
+                sides[lower_side].push((y, x_range.start..split, meta));
+            }
+            if split < x_range.end {
+                sides[1 - lower_side].push((y, split..x_range.end, meta));
+            }
+        }
+        let [first, second] = sides.map(|runs| {
+            PixelArea::from_row_runs(runs, self.color).map(|area| area.with_properties_of(self))
+        });
+        Some([first?, second?])
+    }
+}
+
+/// Sorted positions where the number of covering ranges changes between odd and even
+fn toggle_points<'a>(ranges: impl Iterator<Item = &'a std::ops::Range<u64>>) -> Vec<u64> {
+    let mut points = ranges.flat_map(|r| [r.start, r.end]).collect::<Vec<_>>();
+    points.sort_unstable();
+    let mut toggles: Vec<u64> = Vec::with_capacity(points.len());
+    for point in points {
+        if toggles.last() == Some(&point) {
+            toggles.pop();
+        } else {
+            toggles.push(point);
+        }
+    }
+    toggles
+}
+
+/// Σx² for x in 0..end
+fn sum_of_squares(end: u64) -> f64 {
+    let k = end as f64 - 1.0;
+    k * (k + 1.0) * (2.0 * k + 1.0) / 6.0
+}
+
+type RowRun = (u64, std::ops::Range<u64>, crate::Meta);
+
+/// Each run has 2 vertical edges and 2 horizontal edges per pixel, minus the edges shared
+/// with the runs of the row above and below. Touching runs of a row, which differ only in
+/// confidence, are counted as one run.
+fn perimeter(runs: &[RowRun]) -> u64 {
+    let mut perimeter = 0;
+    let mut previous_row: &[RowRun] = &[];
+    let mut row_start = 0;
+    for idx in 0..=runs.len() {
+        if idx == runs.len() || (idx > 0 && runs[idx].0 != runs[idx - 1].0) {
+            let row = &runs[row_start..idx];
+            if previous_row.first().map(|r| r.0 + 1) == row.first().map(|r| r.0) {
+                perimeter -= 2 * overlap(previous_row, row);
+            }
+            previous_row = row;
+            row_start = idx;
+        }
+        if let Some((_, range, _)) = runs.get(idx) {
+            perimeter += 2 + 2 * (range.end - range.start);
+            if idx > row_start && runs[idx - 1].1.end == range.start {
+                perimeter -= 2;
+            }
+        }
+    }
+    perimeter
+}
+
+fn overlap(a: &[RowRun], b: &[RowRun]) -> u64 {
+    let (mut i, mut j, mut shared) = (0, 0, 0);
+    while let (Some((_, a_range, _)), Some((_, b_range, _))) = (a.get(i), b.get(j)) {
+        let start = a_range.start.max(b_range.start);
+        let end = a_range.end.min(b_range.end);
+        shared += end.saturating_sub(start);
+        if a_range.end < b_range.end {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    shared
+}
+
+/// Andrew's monotone chain over the pixel corners of the first and last run of every row
+fn convex_hull(runs: &[RowRun]) -> Vec<[u32; 2]> {
+    let mut points = Vec::new();
+    for (idx, (y, range, _)) in runs.iter().enumerate() {
+        let first_of_row = idx == 0 || runs[idx - 1].0 != *y;
+        let last_of_row = runs.get(idx + 1).is_none_or(|next| next.0 != *y);
+        let (y, start, end) = (*y as i64, range.start as i64, range.end as i64);
+        if first_of_row {
+            points.extend([[start, y], [start, y + 1]]);
+        }
+        if last_of_row {
+            points.extend([[end, y], [end, y + 1]]);
+        }
+    }
+    points.sort_unstable();
+    points.dedup();
+    if points.len() < 3 {
+        return points.into_iter().map(|p| p.map(|c| c as u32)).collect();
+    }
+
+    let cross = |o: [i64; 2], a: [i64; 2], b: [i64; 2]| {
+        (a[0] - o[0]) * (b[1] - o[1]) - (a[1] - o[1]) * (b[0] - o[0])
+    };
+    let half_hull = |points: &mut dyn Iterator<Item = [i64; 2]>| {
+        let mut hull: Vec<[i64; 2]> = Vec::new();
+        for p in points {
+            while hull.len() >= 2 && cross(hull[hull.len() - 2], hull[hull.len() - 1], p) <= 0 {
+                hull.pop();
+            }
+            hull.push(p);
+        }
+        // The last point is the first point of the other half
+        hull.pop();
+        hull
+    };
+    let mut hull = half_hull(&mut points.iter().copied());
+    hull.extend(half_hull(&mut points.iter().rev().copied()));
+    hull.into_iter().map(|p| p.map(|c| c as u32)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU32;
+
+    use super::*;
+
+    const WIDTH_10: NonZeroU32 = NonZeroU32::new(10).unwrap();
+
+    fn rect(x: u32, y: u32, width: u32, height: u32) -> PixelArea {
+        (y..y + height)
+            .map(|row| {
+                PixelArea::single_range_total_black(
+                    x,
+                    row,
+                    NonZeroU32::new(width).unwrap(),
+                    WIDTH_10,
+                )
+            })
+            .reduce(|a, b| a.union(&b).unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn rectangle_geometry() {
+        let geometry = rect(2, 1, 4, 2).geometry();
+        assert_eq!(geometry.area, 8);
+        assert_eq!(
+            geometry.bounding_box,
+            BoundingBox {
+                min_x: 2,
+                min_y: 1,
+                max_x: 5,
+                max_y: 2
+            }
+        );
+        assert_eq!(geometry.centroid, [3.5, 1.5]);
+        assert_eq!(geometry.perimeter, 12);
+        assert_eq!(geometry.convex_hull, vec![[2, 1], [6, 1], [6, 3], [2, 3]]);
+        assert!(geometry.orientation.abs() < 1e-9);
+        assert!(geometry.eccentricity > 0.8);
+    }
+
+    #[test]
+    fn square_is_not_eccentric() {
+        let geometry = rect(0, 0, 3, 3).geometry();
+        assert!(geometry.eccentricity.abs() < 1e-6);
+        assert_eq!(geometry.perimeter, 12);
+    }
+
+    #[test]
+    fn perimeter_counts_holes() {
+        let ring = rect(0, 0, 3, 3)
+            .difference(&PixelArea::single_range_total_black(
+                1,
+                1,
+                NonZeroU32::MIN,
+                WIDTH_10,
+            ))
+            .unwrap();
+        assert_eq!(ring.geometry().perimeter, 16);
+    }
+
+    #[test]
+    fn perimeter_ignores_confidence() {
+        let soft = PixelArea::from_row_runs(
+            [
+                (0, 0..2, 100),
+                (0, 2..4, 200),
+                (1, 0..1, 50),
+                (1, 1..4, 255),
+            ]
+            .map(|(y, x_range, confidence)| (y, x_range, crate::Meta::new(confidence))),
+            [0, 0, 0],
+        )
+        .unwrap();
+        assert_eq!(
+            soft.geometry().perimeter,
+            rect(0, 0, 4, 2).geometry().perimeter
+        );
+        assert_eq!(soft.geometry().perimeter, 12);
+    }
+
+    #[test]
+    fn outline_joins_collinear_edges() {
+        let mut outline = rect(2, 1, 4, 2).outline();
+        outline.sort_unstable();
+        assert_eq!(
+            outline,
+            vec![
+                [[2, 1], [2, 3]],
+                [[2, 1], [6, 1]],
+                [[2, 3], [6, 3]],
+                [[6, 1], [6, 3]]
+            ]
+        );
+    }
+
+    #[test]
+    fn outline_length_is_perimeter() {
+        let ring = rect(0, 0, 3, 3)
+            .difference(&PixelArea::single_range_total_black(
+                1,
+                1,
+                NonZeroU32::MIN,
+                WIDTH_10,
+            ))
+            .unwrap()
+            .union(&rect(3, 1, 2, 3))
+            .unwrap();
+        let length: u32 = ring
+            .outline()
+            .iter()
+            .map(|[a, b]| b[0] - a[0] + b[1] - a[1])
+            .sum();
+        assert_eq!(length as u64, ring.geometry().perimeter);
+    }
+
+    #[test]
+    fn split_by_line() {
+        let square = rect(0, 0, 4, 4);
+        assert_eq!(
+            square.split_by_line([[2.0, 0.0], [2.0, 4.0]]),
+            Some([rect(0, 0, 2, 4), rect(2, 0, 2, 4)])
+        );
+        assert_eq!(square.split_by_line([[5.0, 0.0], [5.0, 4.0]]), None);
+
+        let [a, b] = square.split_by_line([[0.0, 0.0], [4.0, 3.0]]).unwrap();
+        assert_eq!(a.pixel_count() + b.pixel_count(), 16);
+        assert_eq!(a.intersect(&b), None);
+    }
+
+    #[test]
+    fn vertical_line_orientation() {
+        let geometry = rect(4, 0, 1, 5).geometry();
+        assert!((geometry.orientation.abs() - std::f64::consts::FRAC_PI_2).abs() < 1e-9);
+    }
+}
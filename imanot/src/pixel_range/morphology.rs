@@ -0,0 +1,301 @@
+//! Morphological operations on the row runs of a [`PixelArea`].
+//! Pixels outside of the image are neither grown into nor considered as background, so areas
+//! touching the image border don't shrink from there. Remaining pixels keep their confidence,
+//! grown pixels get the highest confidence of the pixels they were grown from.
+
+use std::{
+    num::{NonZeroU32, NonZeroU64},
+    ops::Range,
+};
+
+use imask::NonZeroRange;
+
+use super::ROW_STRIDE;
+use crate::{Meta, MetaRange, PixelArea};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub enum StructuringElement {
+    /// (2 * radius + 1)² pixels
+    Square { radius: u32 },
+    /// Pixels with a euclidean distance <= radius to the center
+    Disk { radius: u32 },
+}
+
+impl Default for StructuringElement {
+    fn default() -> Self {
+        Self::Disk { radius: 1 }
+    }
+}
+
+impl StructuringElement {
+    pub fn radius(self) -> u32 {
+        match self {
+            StructuringElement::Square { radius } | StructuringElement::Disk { radius } => radius,
+        }
+    }
+
+    /// Horizontal reach to both sides for every vertical offset
+    fn row_extents(self) -> impl Iterator<Item = (i64, u64)> {
+        let radius = self.radius() as i64;
+        (-radius..=radius).map(move |dy| {
+            let extent = match self {
+                StructuringElement::Square { .. } => radius as u64,
+                StructuringElement::Disk { .. } => {
+                    ((radius * radius - dy * dy) as f64).sqrt().floor() as u64
+                }
+            };
+            (dy, extent)
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub enum MorphologyOp {
+    Dilate,
+    Erode,
+    /// Erode followed by dilate: Removes thin protrusions and small islands
+    Open,
+    /// Dilate followed by erode: Closes small gaps and holes
+    Close,
+}
+
+impl MorphologyOp {
+    pub const ALL: [Self; 4] = [Self::Dilate, Self::Erode, Self::Open, Self::Close];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MorphologyOp::Dilate => "Dilate",
+            MorphologyOp::Erode => "Erode",
+            MorphologyOp::Open => "Open",
+            MorphologyOp::Close => "Close",
+        }
+    }
+}
+
+impl PixelArea {
+    /// `image_size` is [width, height]
+    pub fn morphology(
+        &self,
+        op: MorphologyOp,
+        element: StructuringElement,
+        image_size: [NonZeroU32; 2],
+    ) -> Option<PixelArea> {
+        match op {
+            MorphologyOp::Dilate => self.dilate(element, image_size),
+            MorphologyOp::Erode => self.erode(element, image_size),
+            MorphologyOp::Open => self.erode(element, image_size)?.dilate(element, image_size),
+            MorphologyOp::Close => self.dilate(element, image_size)?.erode(element, image_size),
+        }
+    }
+
+    /// Grows the area by `element`, without leaving the image
+    pub fn dilate(
+        &self,
+        element: StructuringElement,
+        image_size: [NonZeroU32; 2],
+    ) -> Option<PixelArea> {
+        let [width, height] = image_size.map(|x| x.get() as u64);
+        let grown = self
+            .row_runs()
+            .flat_map(|(y, x_range, meta)| {
+                element.row_extents().filter_map(move |(dy, extent)| {
+                    let y = y as i64 + dy;
+                    (0..height as i64).contains(&y).then(|| {
+                        let row_start = y as u64 * ROW_STRIDE;
+                        let start = x_range.start.saturating_sub(extent);
+                        let end = (x_range.end + extent).min(width);
+                        (row_start + start..row_start + end, meta)
+                    })
+                })
+            })
+            .collect::<Vec<_>>();
+        let grown = self.with_runs(highest_confidence(grown))?;
+        // Disjoint, so the original pixels keep their confidence
+        match grown.difference(self) {
+            Some(added) => self.union(&added),
+            None => Some(self.clone()),
+        }
+    }
+
+    /// Shrinks the area by `element`, which is dilating the complement. Only the complement
+    /// within reach of the area is considered, so the cost depends on the area, not the image.
+    pub fn erode(
+        &self,
+        element: StructuringElement,
+        image_size: [NonZeroU32; 2],
+    ) -> Option<PixelArea> {
+        let bounds = expanded_roi(self, element.radius(), image_size);
+        match self.invert(bounds) {
+            Some(background) => {
+                let shape = background.dilate(element, image_size)?.invert(bounds)?;
+                self.intersect(&shape)
+            }
+            // Covers everything within reach, nothing to shrink from
+            None => Some(self.clone()),
+        }
+    }
+}
+
+pub(crate) fn image_rect([width, height]: [NonZeroU32; 2]) -> imask::Rect<u64> {
+    imask::Rect::<u64>::new(0, 0, NonZeroU64::from(width), NonZeroU64::from(height))
+}
+
+/// Region of interest of `area` grown by `radius` to all sides, clipped to the image
+fn expanded_roi(
+    area: &PixelArea,
+    radius: u32,
+    [width, height]: [NonZeroU32; 2],
+) -> imask::Rect<u64> {
+    let roi = area.roi();
+    let radius = radius as u64;
+    let (x, y) = (roi.x as u64, roi.y as u64);
+    let (min_x, min_y) = (x.saturating_sub(radius), y.saturating_sub(radius));
+    let max_x = (x + roi.width.get() as u64 + radius).min(width.get() as u64);
+    let max_y = (y + roi.height.get() as u64 + radius).min(height.get() as u64);
+    imask::Rect::<u64>::new(
+        min_x,
+        min_y,
+        NonZeroU64::new(max_x - min_x).expect("Contains the region of interest"),
+        NonZeroU64::new(max_y - min_y).expect("Contains the region of interest"),
+    )
+}
+
+/// Union of possibly overlapping ranges, where every pixel gets the highest confidence of
+/// the ranges containing it
+fn highest_confidence(ranges: Vec<(Range<u64>, Meta)>) -> Vec<MetaRange> {
+    let mut events = ranges
+        .into_iter()
+        .flat_map(|(range, meta)| {
+            [
+                (range.start, true, meta.confidence()),
+                (range.end, false, meta.confidence()),
+            ]
+        })
+        .collect::<Vec<_>>();
+    events.sort_unstable_by_key(|(pos, ..)| *pos);
+
+    // Number of ranges covering the current position by confidence
+    let mut active = [0u32; 256];
+    let mut result: Vec<MetaRange> = Vec::new();
+    let mut last_pos = 0;
+    for (pos, is_start, confidence) in events {
+        let highest = (0..=u8::MAX).rev().find(|c| active[*c as usize] > 0);
+        if let Some(highest) = highest
+            && pos > last_pos
+        {
+            let meta = Meta::new(highest);
+            match result.last_mut() {
+                Some(last) if last.range.end == last_pos && last.meta == meta => {
+                    let len = NonZeroU64::new(pos - last.range.start).expect("pos > start");
+                    last.range = NonZeroRange::from_span(last.range.start, len);
+                }
+                _ => result.push(MetaRange {
+                    range: NonZeroRange::from_span(
+                        last_pos,
+                        NonZeroU64::new(pos - last_pos).expect("pos > last_pos"),
+                    ),
+                    meta,
+                }),
+            }
+        }
+        if is_start {
+            active[confidence as usize] += 1;
+        } else {
+            active[confidence as usize] -= 1;
+        }
+        last_pos = pos;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH_7: NonZeroU32 = NonZeroU32::new(7).unwrap();
+    const IMAGE_7: [NonZeroU32; 2] = [WIDTH_7, WIDTH_7];
+
+    fn pixels(area: Option<&PixelArea>) -> Vec<u64> {
+        area.into_iter()
+            .flat_map(|a| a.global_ranges(WIDTH_7))
+            .flat_map(|(range, _)| range)
+            .collect()
+    }
+
+    fn center() -> PixelArea {
+        PixelArea::single_range_total_black(3, 3, NonZeroU32::MIN, WIDTH_7)
+    }
+
+    #[test]
+    fn dilate_square_and_disk() {
+        let square = center().dilate(StructuringElement::Square { radius: 1 }, IMAGE_7);
+        assert_eq!(
+            pixels(square.as_ref()),
+            vec![16, 17, 18, 23, 24, 25, 30, 31, 32]
+        );
+        let disk = center().dilate(StructuringElement::Disk { radius: 1 }, IMAGE_7);
+        assert_eq!(pixels(disk.as_ref()), vec![17, 23, 24, 25, 31]);
+    }
+
+    #[test]
+    fn dilate_is_clipped_at_image_border() {
+        let corner = PixelArea::single_range_total_black(0, 0, NonZeroU32::MIN, WIDTH_7);
+        let grown = corner.dilate(StructuringElement::Square { radius: 1 }, IMAGE_7);
+        assert_eq!(pixels(grown.as_ref()), vec![0, 1, 7, 8]);
+    }
+
+    #[test]
+    fn erode_reverts_dilate_of_convex_area() {
+        let element = StructuringElement::Square { radius: 1 };
+        let grown = center().dilate(element, IMAGE_7).unwrap();
+        let shrunk = grown.erode(element, IMAGE_7);
+        assert_eq!(pixels(shrunk.as_ref()), vec![24]);
+        assert_eq!(shrunk.unwrap().erode(element, IMAGE_7), None);
+    }
+
+    #[test]
+    fn confidence_is_carried_through() {
+        let element = StructuringElement::Square { radius: 1 };
+        let low = PixelArea::from_row_runs([(3, 3..4, Meta::new(100))], [0, 0, 0]).unwrap();
+        let grown = low.dilate(element, IMAGE_7).unwrap();
+        assert!(
+            grown
+                .row_runs()
+                .all(|(_, _, meta)| meta.confidence() == 100)
+        );
+
+        // (3, 3) keeps its confidence, although it is within reach of (4, 3)
+        let mixed = PixelArea::from_row_runs(
+            [(3, 3..4, Meta::new(50)), (3, 4..5, Meta::new(200))],
+            [0, 0, 0],
+        )
+        .unwrap();
+        let grown = mixed.dilate(element, IMAGE_7).unwrap();
+        let confidences = grown
+            .row_runs()
+            .filter(|(y, _, _)| *y == 3)
+            .flat_map(|(_, x_range, meta)| x_range.map(move |_| meta.confidence()))
+            .collect::<Vec<_>>();
+        assert_eq!(confidences, [50, 50, 200, 200]);
+        let shrunk = grown.erode(element, IMAGE_7).unwrap();
+        assert_eq!(
+            shrunk
+                .row_runs()
+                .map(|(_, x, m)| (x, m.confidence()))
+                .collect::<Vec<_>>(),
+            [(3..4, 50), (4..5, 200)]
+        );
+    }
+
+    #[test]
+    fn open_removes_single_pixels() {
+        let element = StructuringElement::Square { radius: 1 };
+        let block = center().dilate(element, IMAGE_7).unwrap();
+        let noise = PixelArea::single_range_total_black(6, 0, NonZeroU32::MIN, WIDTH_7);
+        let noisy = block.union(&noise).unwrap();
+        let opened = noisy.morphology(MorphologyOp::Open, element, IMAGE_7);
+        assert_eq!(pixels(opened.as_ref()), pixels(Some(&block)));
+    }
+}
@@ -0,0 +1,321 @@
+//! Set operations working directly on the sorted runs of two [`PixelArea`]s.
+//! All results keep the color and the other properties of `self`, e.g. class, kind, parent and
+//! attributes. `None` is returned for empty results.
+
+use std::{num::NonZeroU64, ops::Range};
+
+use imask::NonZeroRange;
+
+use super::ROW_STRIDE;
+use crate::{Meta, MetaRange, PixelArea};
+
+impl PixelArea {
+    /// Pixels in `self` or `other`. Overlapping pixels keep the higher confidence.
+    pub fn union(&self, other: &PixelArea) -> Option<PixelArea> {
+        self.combine(other, |a, b| a.max(b))
+    }
+
+    /// Pixels in `self` and `other` with the lower confidence of both
+    pub fn intersect(&self, other: &PixelArea) -> Option<PixelArea> {
+        self.combine(other, |a, b| a.zip(b).map(|(a, b)| a.min(b)))
+    }
+
+    /// Pixels in `self` but not in `other`
+    pub fn difference(&self, other: &PixelArea) -> Option<PixelArea> {
+        self.combine(other, |a, b| a.filter(|_| b.is_none()))
+    }
+
+    /// Pixels in exactly one of `self` and `other`
+    pub fn symmetric_difference(&self, other: &PixelArea) -> Option<PixelArea> {
+        self.combine(other, |a, b| a.xor(b))
+    }
+
+    /// All pixels of `self`, where those also in `footprint` have their confidence changed by
+    /// `delta`, saturating at the bounds
+    pub fn adjust_confidence(&self, footprint: &PixelArea, delta: i16) -> Option<PixelArea> {
+        self.combine(footprint, |a, b| match (a, b) {
+            (Some(meta), Some(_)) => Some(Meta::new(
+                (meta.confidence() as i16 + delta).clamp(0, u8::MAX as i16) as u8,
+            )),
+            (a, _) => a,
+        })
+    }
+
+    /// Pixels within `within_bounds` which are not part of `self`
+    pub fn invert(&self, within_bounds: imask::Rect<u64>) -> Option<PixelArea> {
+        let bounds = within_bounds
+            .into_rect_iter(NonZeroU64::new(ROW_STRIDE).expect("Not zero"))
+            .map(|range| (range.start..range.end, Meta::default()))
+            .collect::<Vec<_>>();
+        let ranges = combine_runs(&bounds, &self.runs(), |a, b| a.filter(|_| b.is_none()));
+        self.with_runs(ranges)
+    }
+
+    /// Half open pixel ranges at `y * ROW_STRIDE + x` with their meta
+    pub(crate) fn runs(&self) -> Vec<(Range<u64>, Meta)> {
+        self.row_runs()
+            .map(|(y, x_range, meta)| {
+                let row_start = y * ROW_STRIDE;
+                (row_start + x_range.start..row_start + x_range.end, meta)
+            })
+            .collect()
+    }
+
+    /// Creates an area with the color and the other properties of `self` from sorted, disjoint
+    /// runs at `y * ROW_STRIDE + x`
+    pub(crate) fn with_runs(&self, runs: Vec<MetaRange>) -> Option<PixelArea> {
+        PixelArea::from_row_runs(
+            runs.into_iter().map(|MetaRange { range, meta }| {
+                let y = range.start / ROW_STRIDE;
+                let row_start = y * ROW_STRIDE;
+                (y, range.start - row_start..range.end - row_start, meta)
+            }),
+            self.color,
+        )
+        .map(|x| x.with_properties_of(self))
+    }
+
+    fn combine(
+        &self,
+        other: &PixelArea,
+        op: impl Fn(Option<Meta>, Option<Meta>) -> Option<Meta>,
+    ) -> Option<PixelArea> {
+        self.with_runs(combine_runs(&self.runs(), &other.runs(), op))
+    }
+}
+
+/// Walks over the sorted runs of `a` and `b` in a single merge pass. `op` decides the meta of
+/// each segment between consecutive run boundaries, given the meta of `a` and `b` there.
+/// Adjacent segments with equal meta are merged.
+fn combine_runs(
+    a: &[(Range<u64>, Meta)],
+    b: &[(Range<u64>, Meta)],
+    op: impl Fn(Option<Meta>, Option<Meta>) -> Option<Meta>,
+) -> Vec<MetaRange> {
+    /// Skips the runs ending at or before `pos`. Returns the meta at `pos` and the next boundary
+    /// after it.
+    fn advance(
+        runs: &[(Range<u64>, Meta)],
+        idx: &mut usize,
+        pos: u64,
+    ) -> (Option<Meta>, Option<u64>) {
+        while runs.get(*idx).is_some_and(|(range, _)| range.end <= pos) {
+            *idx += 1;
+        }
+        match runs.get(*idx) {
+            Some((range, meta)) if range.start <= pos => (Some(*meta), Some(range.end)),
+            Some((range, _)) => (None, Some(range.start)),
+            None => (None, None),
+        }
+    }
+
+    let Some(mut start) = [a.first(), b.first()]
+        .into_iter()
+        .flatten()
+        .map(|(range, _)| range.start)
+        .min()
+    else {
+        return Vec::new();
+    };
+    let (mut idx_a, mut idx_b) = (0, 0);
+    let mut result: Vec<MetaRange> = Vec::new();
+    loop {
+        let (meta_a, next_a) = advance(a, &mut idx_a, start);
+        let (meta_b, next_b) = advance(b, &mut idx_b, start);
+        let Some(end) = [next_a, next_b].into_iter().flatten().min() else {
+            break;
+        };
+        if let Some(meta) = op(meta_a, meta_b) {
+            match result.last_mut() {
+                Some(last) if last.range.end == start && last.meta == meta => {
+                    let len = NonZeroU64::new(end - last.range.start).expect("end > start");
+                    last.range = NonZeroRange::from_span(last.range.start, len);
+                }
+                _ => result.push(MetaRange {
+                    range: NonZeroRange::from_span(
+                        start,
+                        NonZeroU64::new(end - start).expect("Boundaries come after start"),
+                    ),
+                    meta,
+                }),
+            }
+        }
+        start = end;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU32;
+
+    use imask::ImaskSet;
+
+    use super::*;
+
+    const WIDTH: u32 = 8;
+    const HEIGHT: u32 = 6;
+
+    /// Small xorshift generator, so the property tests are reproducible
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn bits(&mut self) -> Vec<bool> {
+            // Long runs are more likely than with independent pixels
+            let mut value = false;
+            (0..WIDTH * HEIGHT)
+                .map(|_| {
+                    if self.next() % 3 == 0 {
+                        value = !value;
+                    }
+                    value
+                })
+                .collect()
+        }
+    }
+
+    fn area_from_bits(bits: &[bool]) -> Option<PixelArea> {
+        let mut ranges: Vec<MetaRange> = Vec::new();
+        for (pos, _) in bits.iter().enumerate().filter(|(_, set)| **set) {
+            let pos = pos as u64;
+            match ranges.last_mut() {
+                Some(last) if last.range.end == pos => last.range.increment_length(),
+                _ => ranges.push(MetaRange {
+                    range: NonZeroRange::from_span(pos, NonZeroU64::MIN),
+                    meta: Meta::default(),
+                }),
+            }
+        }
+        PixelArea::with_black_color(ranges.with_bounds(
+            NonZeroU32::new(WIDTH).unwrap(),
+            NonZeroU32::new(HEIGHT).unwrap(),
+        ))
+    }
+
+    fn bits_from_area(area: Option<&PixelArea>) -> Vec<bool> {
+        let mut bits = vec![false; (WIDTH * HEIGHT) as usize];
+        for (range, _) in area
+            .into_iter()
+            .flat_map(|a| a.global_ranges(NonZeroU32::new(WIDTH).unwrap()))
+        {
+            bits[range.start as usize..range.end as usize].fill(true);
+        }
+        bits
+    }
+
+    fn check(
+        op: impl Fn(&PixelArea, &PixelArea) -> Option<PixelArea>,
+        expected: fn(bool, bool) -> bool,
+    ) {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..200 {
+            let (bits_a, bits_b) = (rng.bits(), rng.bits());
+            let (Some(a), Some(b)) = (area_from_bits(&bits_a), area_from_bits(&bits_b)) else {
+                continue;
+            };
+            let result = op(&a, &b);
+            let expected_bits = bits_a
+                .iter()
+                .zip(&bits_b)
+                .map(|(a, b)| expected(*a, *b))
+                .collect::<Vec<_>>();
+            assert_eq!(bits_from_area(result.as_ref()), expected_bits);
+            // Runs must be maximal, otherwise equality with other areas fails
+            assert_eq!(result, area_from_bits(&expected_bits));
+        }
+    }
+
+    #[test]
+    fn union_matches_bitwise_or() {
+        check(PixelArea::union, |a, b| a | b);
+    }
+
+    #[test]
+    fn intersect_matches_bitwise_and() {
+        check(PixelArea::intersect, |a, b| a & b);
+    }
+
+    #[test]
+    fn difference_matches_and_not() {
+        check(PixelArea::difference, |a, b| a & !b);
+    }
+
+    #[test]
+    fn symmetric_difference_matches_xor() {
+        check(PixelArea::symmetric_difference, |a, b| a ^ b);
+    }
+
+    #[test]
+    fn adjust_confidence_only_within_footprint() {
+        let width = NonZeroU32::new(WIDTH).unwrap();
+        let area = PixelArea::single_range_total_black(0, 1, NonZeroU32::new(6).unwrap(), width);
+        let footprint =
+            PixelArea::single_range_total_black(4, 1, NonZeroU32::new(4).unwrap(), width);
+        let lowered = area.adjust_confidence(&footprint, -100).unwrap();
+        let runs = lowered.row_runs().collect::<Vec<_>>();
+        assert_eq!(
+            runs,
+            [
+                (1, 0..4, Meta::default()),
+                (1, 4..6, Meta::new(u8::MAX - 100)),
+            ]
+        );
+
+        let raised = lowered.adjust_confidence(&footprint, 200).unwrap();
+        assert_eq!(raised, area);
+    }
+
+    #[test]
+    fn invert_twice_is_identity() {
+        let full = || {
+            imask::Rect::<u64>::new(
+                0,
+                0,
+                NonZeroU64::new(WIDTH as u64).unwrap(),
+                NonZeroU64::new(HEIGHT as u64).unwrap(),
+            )
+        };
+        let mut rng = Rng(7);
+        for _ in 0..50 {
+            let bits = rng.bits();
+            let Some(area) = area_from_bits(&bits) else {
+                continue;
+            };
+            let inverted = area.invert(full());
+            let inverted_bits = bits_from_area(inverted.as_ref());
+            assert!(bits.iter().zip(&inverted_bits).all(|(a, b)| a != b));
+            if let Some(inverted) = inverted {
+                assert_eq!(inverted.invert(full()), Some(area));
+            }
+        }
+    }
+
+    #[test]
+    fn invert_within_bounds() {
+        let area = PixelArea::single_range_total_black(
+            2,
+            1,
+            NonZeroU32::new(2).unwrap(),
+            NonZeroU32::new(WIDTH).unwrap(),
+        );
+        let bounds = imask::Rect::<u64>::new(
+            1,
+            1,
+            NonZeroU64::new(4).unwrap(),
+            NonZeroU64::new(2).unwrap(),
+        );
+        let inverted = area.invert(bounds);
+        let mut expected = vec![false; (WIDTH * HEIGHT) as usize];
+        for pos in [9, 12, 17, 18, 19, 20] {
+            expected[pos] = true;
+        }
+        assert_eq!(bits_from_area(inverted.as_ref()), expected);
+    }
+}
@@ -50,6 +50,22 @@ impl State {
                 .ui(ui, self.image_state.sources(ui.ctx()), Some(Sense::click()));
         let result = InnerResponse {
             inner: if let Some(mut r) = inner {
+                if let crate::ImageState::Loaded(image) = &mut self.image_state {
+                    let masks = &mut image.masks;
+                    masks.paint_outlines(&r.image_painter);
+                    let hovered = r
+                        .cursor_image_pos
+                        .filter(|_| masks.is_visible())
+                        .and_then(|(x, y)| masks.area_at([x as u32, y as u32]));
+                    masks.paint_highlights(&r.image_painter, hovered);
+                    if let Some(layer) = hovered
+                        && !response.dragged()
+                    {
+                        response
+                            .clone()
+                            .on_hover_ui_at_pointer(|ui| masks.area_info_ui(ui, layer));
+                    }
+                }
                 self.handle_tool_interaction(&response, ui.ctx(), &mut r.image_painter);
                 Some(r)
             } else {
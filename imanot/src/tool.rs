@@ -1,14 +1,18 @@
 mod clear;
+mod confidence_brush;
 mod pan;
 mod rect;
 mod rect_selection;
+mod split;
 
 pub use clear::*;
+pub use confidence_brush::*;
 pub use pan::*;
 pub use rect::*;
 pub use rect_selection::*;
+pub use split::*;
 
-use crate::{CursorImageSystem, ImageStateLoaded, ImageViewer};
+use crate::{AddMode, CursorImageSystem, ImageStateLoaded, ImageViewer};
 
 pub trait Tool {
     fn handle_interaction(&mut self, ctx: ToolContext);
@@ -135,6 +139,15 @@ impl<'a> ToolContext<'a> {
         }
     }
 
+    /// Overwrite existing masks while Alt is held, otherwise keep them
+    pub fn add_mode(&self) -> AddMode {
+        if self.egui.input(|i| i.modifiers.alt) {
+            AddMode::Overwrite
+        } else {
+            AddMode::KeepExisting
+        }
+    }
+
     /// Get the cursor position in image coordinates (pixels)
     /// Returns None if the cursor is not over the image
     pub fn cursor_image_pos(&self) -> Option<(usize, usize)> {
@@ -1,9 +1,8 @@
-use std::num::{NonZero, NonZeroU64};
+use std::num::NonZeroU32;
 
 use futures::FutureExt;
-use imask::{ImaskSet, NonZeroRange};
 
-use crate::{RectSelection, Tool, ToolContext, ToolFactory};
+use crate::{Meta, PixelArea, RectSelection, Tool, ToolContext, ToolFactory};
 
 #[derive(Default)]
 #[non_exhaustive]
@@ -21,18 +20,20 @@ impl Tool for ClearTool {
     fn handle_interaction(&mut self, mut ctx: ToolContext) {
         let selection = self.rect_selection.drag_finished(&mut ctx);
         if let Some(region) = selection {
-            ctx.image.masks.clear_ranges(region.iter_ranges());
+            if let Some(area) = region.into_pixel_area(Meta::default(), [0, 0, 0]) {
+                ctx.image.masks.clear_area(area);
+            }
         } else if ctx.response.clicked()
             && let Some((x, y)) = ctx.cursor_image_pos()
         {
-            let image_width = ctx.image.image.original.width();
-            let pos = y as u64 * image_width.get() as u64 + x as u64;
-            let range = NonZeroRange::from_span(pos, NonZeroU64::MIN);
-            let height = NonZero::new(u32::try_from(y).unwrap()).unwrap();
-            let width = ctx.image.image.original.width();
-            let single_pixel = std::iter::once(range).with_bounds(width, height);
-
-            ctx.image.masks.clear_ranges(single_pixel);
+            let single_pixel = PixelArea::single_pixel_total_color(
+                x.try_into().unwrap(),
+                y.try_into().unwrap(),
+                NonZeroU32::MIN,
+                [0, 0, 0],
+                ctx.image.image.original.width(),
+            );
+            ctx.image.masks.clear_area(single_pixel);
         }
     }
 }
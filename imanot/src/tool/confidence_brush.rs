@@ -0,0 +1,128 @@
+use egui::{Color32, Pos2, Stroke};
+use futures::FutureExt;
+
+use crate::{Meta, PixelArea, Tool, ToolContext, ToolFactory};
+
+/// Raises the confidence of all mask pixels painted over, or lowers it while Shift is held.
+/// A stroke is a single undoable change, applied when the mouse button is released.
+#[non_exhaustive]
+pub struct ConfidenceBrushTool {
+    /// Radius of the brush in image pixels
+    radius: f32,
+    /// Confidence change of every stroke
+    step: u8,
+    /// Pixels painted over since the stroke started
+    stroke: Option<PixelArea>,
+    /// Last brush position of the stroke (in image pixel coordinates)
+    last_pos: Option<Pos2>,
+}
+
+impl Default for ConfidenceBrushTool {
+    fn default() -> Self {
+        Self {
+            radius: 8.0,
+            step: 32,
+            stroke: None,
+            last_pos: None,
+        }
+    }
+}
+
+impl ConfidenceBrushTool {
+    pub fn set_radius(&mut self, radius: f32) -> &mut Self {
+        self.radius = radius.max(0.5);
+        self
+    }
+
+    pub fn set_step(&mut self, step: u8) -> &mut Self {
+        self.step = step;
+        self
+    }
+
+    pub fn create_factory() -> ToolFactory {
+        Self::create_factory_with(|_| ())
+    }
+
+    pub fn create_factory_with(
+        modifier: impl Fn(&mut ConfidenceBrushTool) + 'static,
+    ) -> ToolFactory {
+        Box::new(move |_| {
+            let mut tool = ConfidenceBrushTool::default();
+            modifier(&mut tool);
+            async { Ok(Box::new(tool) as Box<dyn Tool>) }.boxed_local()
+        })
+    }
+
+    /// Adds disks from the last position to `pos`, so fast strokes have no gaps
+    fn paint_to(&mut self, pos: Pos2, [width, height]: [u32; 2]) {
+        let from = self.last_pos.unwrap_or(pos);
+        let steps = ((pos - from).length() / (self.radius / 2.0))
+            .ceil()
+            .max(1.0) as usize;
+        for step in 1..=steps {
+            let center = from.lerp(pos, step as f32 / steps as f32);
+            let Some(disk) = disk(center, self.radius, [width, height]) else {
+                continue;
+            };
+            self.stroke = match self.stroke.take() {
+                Some(stroke) => stroke.union(&disk),
+                None => Some(disk),
+            };
+        }
+        self.last_pos = Some(pos);
+    }
+}
+
+impl Tool for ConfidenceBrushTool {
+    fn handle_interaction(&mut self, mut ctx: ToolContext) {
+        let hover = ctx
+            .response
+            .hover_pos()
+            .or(ctx.response.interact_pointer_pos());
+        if let Some(screen_pos) = hover {
+            let lower = ctx.egui.input(|i| i.modifiers.shift);
+            let color = if lower { Color32::RED } else { Color32::GREEN };
+            ctx.painter.painter().circle_stroke(
+                screen_pos,
+                self.radius * ctx.painter.render_scale(),
+                Stroke::new(1.5, color),
+            );
+        }
+
+        let painting = ctx.response.dragged() || ctx.response.clicked();
+        if painting && let Some(screen_pos) = ctx.response.interact_pointer_pos() {
+            let pos = ctx.painter.screen_to_image(screen_pos);
+            let size = ctx.image.masks.image_size().map(|x| x.get());
+            self.paint_to(pos, size);
+        }
+
+        if ctx.response.drag_stopped() || ctx.response.clicked() {
+            self.last_pos = None;
+            if let Some(stroke) = self.stroke.take() {
+                let delta = if ctx.egui.input(|i| i.modifiers.shift) {
+                    -(self.step as i16)
+                } else {
+                    self.step as i16
+                };
+                ctx.image.masks.adjust_confidence(stroke, delta);
+            }
+        }
+    }
+}
+
+/// Pixels whose centers lie within the circle, clipped to the image
+fn disk(center: Pos2, radius: f32, [width, height]: [u32; 2]) -> Option<PixelArea> {
+    let min_y = (center.y - radius).floor().max(0.0) as u64;
+    let max_y = ((center.y + radius).ceil().max(0.0) as u64).min(height as u64);
+    let runs = (min_y..max_y).filter_map(|y| {
+        let dy = y as f32 + 0.5 - center.y;
+        let dx = (radius * radius - dy * dy).sqrt();
+        if dx.is_nan() {
+            return None;
+        }
+        let start = (center.x - dx - 0.5).ceil().max(0.0) as u64;
+        let end = ((center.x + dx + 0.5).floor().max(0.0) as u64).min(width as u64);
+        (start < end).then_some((y, start..end, Meta::default()))
+    });
+    PixelArea::from_row_runs(runs, [0, 0, 0])
+}
@@ -3,7 +3,8 @@ use futures::FutureExt;
 
 use crate::{Tool, ToolContext, ToolFactory};
 
-/// Pan tool for moving the viewport around the image
+/// Pan tool for moving the viewport around the image.
+/// Clicking selects the topmost mask under the cursor.
 #[derive(Default)]
 #[non_exhaustive]
 pub struct PanTool;
@@ -15,9 +16,9 @@ impl PanTool {
 }
 
 impl Tool for PanTool {
-    fn handle_interaction(&mut self, ctx: ToolContext) {
+    fn handle_interaction(&mut self, mut ctx: ToolContext) {
         // Panning logic will be moved here from ImageViewer
-        let viewer = ctx.viewer;
+        let viewer = &mut *ctx.viewer;
         let response = &ctx.response;
 
         let drag_delta = response.drag_delta();
@@ -57,6 +58,13 @@ impl Tool for PanTool {
             }
 
             viewer.set_pan_offset(new_offset);
+        } else if response.clicked() {
+            let pos = ctx.cursor_image_pos();
+            let masks = &mut ctx.image.masks;
+            let selected = pos
+                .filter(|_| masks.is_visible())
+                .and_then(|(x, y)| masks.area_at([x as u32, y as u32]));
+            masks.set_selected(selected);
         }
     }
 }
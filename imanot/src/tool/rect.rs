@@ -2,7 +2,9 @@ use std::num::NonZeroU32;
 
 use futures::FutureExt;
 
-use crate::{CursorImage, Meta, PixelArea, RectSelection, Tool, ToolContext, ToolFactory};
+use crate::{
+    AreaKind, CursorImage, Meta, PixelArea, RectSelection, Tool, ToolContext, ToolFactory,
+};
 
 // https://www.svgrepo.com/svg/437030/lasso
 const RECT_CURSOR_IMAGE: CursorImage = CursorImage {
@@ -17,6 +19,7 @@ pub struct RectTool {
     rect_selection: RectSelection,
     layer: Option<usize>,
     fix_color: Option<[u8; 3]>,
+    kind: AreaKind,
 }
 
 impl RectTool {
@@ -25,6 +28,12 @@ impl RectTool {
         self
     }
 
+    /// E.g. [`AreaKind::Ignore`] to draw ignore regions
+    pub fn set_kind(&mut self, kind: AreaKind) -> &mut Self {
+        self.kind = kind;
+        self
+    }
+
     pub fn set_color(&mut self, color: [u8; 3]) -> &mut Self {
         self.fix_color = Some(color);
         self
@@ -49,6 +58,7 @@ impl RectTool {
                     rect_selection: RectSelection::default(),
                     fix_color: Some(color),
                     layer: None,
+                    kind: AreaKind::default(),
                 }) as Box<dyn Tool>)
             }
             .boxed_local()
@@ -61,32 +71,29 @@ impl Tool for RectTool {
         ctx.cursor_image.set(RECT_CURSOR_IMAGE);
 
         let selection = self.rect_selection.drag_finished(&mut ctx);
-        if let Some(rect_result) = selection {
-            let color = self
-                .fix_color
-                .unwrap_or_else(|| ctx.image.masks.next_color());
-            if let Some(pixel_area) = rect_result.into_pixel_area(Meta::default(), color) {
-                ctx.image
-                    .masks
-                    .add_area_non_overlapping_parts_at(pixel_area, self.layer);
-            }
+        let pixel_area = if let Some(rect_result) = selection {
+            rect_result.into_pixel_area(Meta::default(), [0, 0, 0])
         } else if ctx.response.clicked()
             && let Some((x, y)) = ctx.cursor_image_pos()
         {
-            let color = self
-                .fix_color
-                .unwrap_or_else(|| ctx.image.masks.next_color());
             let image_width = ctx.image.image.original.width();
-            let pixel_area = PixelArea::single_pixel_total_color(
+            Some(PixelArea::single_pixel_total_color(
                 x.try_into().unwrap(),
                 y.try_into().unwrap(),
                 NonZeroU32::MIN,
-                color,
+                [0, 0, 0],
                 image_width,
-            );
-            ctx.image
-                .masks
-                .add_area_non_overlapping_parts_at(pixel_area, self.layer);
+            ))
+        } else {
+            None
+        };
+        if let Some(pixel_area) = pixel_area {
+            let mut pixel_area = pixel_area.with_kind(self.kind);
+            pixel_area.color = self
+                .fix_color
+                .unwrap_or_else(|| ctx.image.masks.color_for(&pixel_area));
+            let mode = ctx.add_mode();
+            ctx.image.masks.add_area_at(pixel_area, self.layer, mode);
         }
     }
 }
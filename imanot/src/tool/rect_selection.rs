@@ -81,7 +81,11 @@ impl RectSelectionResult {
 
     /// Convert to a PixelArea with the given meta and color
     pub fn into_pixel_area(self, meta: Meta, color: [u8; 3]) -> Option<PixelArea> {
-        PixelArea::new(self.iter_ranges_meta(meta), color)
+        let x_range = self.min_x as u64..self.max_x as u64 + 1;
+        PixelArea::from_row_runs(
+            (self.min_y as u64..=self.max_y as u64).map(|y| (y, x_range.clone(), meta)),
+            color,
+        )
     }
 }
 
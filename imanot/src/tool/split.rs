@@ -0,0 +1,58 @@
+use egui::Pos2;
+use futures::FutureExt;
+
+use crate::{Tool, ToolContext, ToolFactory};
+
+/// Splits a mask in two along a dragged line.
+/// The selected mask is split, otherwise the topmost mask where the drag started.
+#[derive(Default)]
+#[non_exhaustive]
+pub struct SplitTool {
+    /// Image position where drag started (in image pixel coordinates)
+    drag_start_image: Option<Pos2>,
+}
+
+impl SplitTool {
+    pub fn create_factory() -> ToolFactory {
+        Box::new(|_| async { Ok(Box::new(SplitTool::default()) as Box<dyn Tool>) }.boxed_local())
+    }
+}
+
+impl Tool for SplitTool {
+    fn handle_interaction(&mut self, mut ctx: ToolContext) {
+        if ctx.response.drag_started() {
+            let drag_delta = ctx.response.drag_delta();
+            self.drag_start_image = ctx
+                .response
+                .interact_pointer_pos()
+                .map(|screen_pos| ctx.painter.screen_to_image(screen_pos - drag_delta));
+        }
+        let Some(start) = self.drag_start_image else {
+            return;
+        };
+        let Some(end) = ctx
+            .response
+            .interact_pointer_pos()
+            .map(|screen_pos| ctx.painter.screen_to_image(screen_pos))
+        else {
+            return;
+        };
+
+        if ctx.response.dragged() {
+            ctx.painter.draw_dotted_line(
+                ctx.painter.image_to_screen(start),
+                ctx.painter.image_to_screen(end),
+            );
+        }
+        if ctx.response.drag_stopped() {
+            self.drag_start_image = None;
+            let masks = &mut ctx.image.masks;
+            let layer = masks
+                .selected()
+                .or_else(|| masks.area_at([start.x as u32, start.y as u32]));
+            if let Some(layer) = layer {
+                masks.split_along_line(layer, [[start.x, start.y], [end.x, end.y]]);
+            }
+        }
+    }
+}
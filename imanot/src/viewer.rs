@@ -1,10 +1,34 @@
 use egui::{
-    self, ImageSource, InnerResponse, Pos2, Rect, Sense, TextureOptions, Vec2,
+    self, Color32, ImageSource, InnerResponse, Pos2, Rect, Sense, TextureOptions, Vec2,
     load::{SizedTexture, TexturePoll},
 };
 
 use crate::ImagePainter;
 
+/// Image drawn by the [`ImageViewer`]. The texture is multiplied with `tint`, e.g. to apply opacity.
+pub struct ViewerLayer {
+    pub source: ImageSource<'static>,
+    pub tint: Color32,
+}
+
+impl ViewerLayer {
+    pub fn with_opacity(source: ImageSource<'static>, opacity: f32) -> Self {
+        Self {
+            source,
+            tint: Color32::WHITE.gamma_multiply(opacity.clamp(0.0, 1.0)),
+        }
+    }
+}
+
+impl From<ImageSource<'static>> for ViewerLayer {
+    fn from(source: ImageSource<'static>) -> Self {
+        Self {
+            source,
+            tint: Color32::WHITE,
+        }
+    }
+}
+
 pub struct ImageViewer {
     // Zoom level (0.05..1.0)
     // 1.0 means, that image width or height fits the viewport and the other dimension is smaller than the viewport
@@ -42,6 +66,14 @@ impl ImageViewer {
         self.pan_offset = offset;
     }
 
+    /// Zooms and pans so `region` (in image pixels) is centered with some margin around it
+    pub fn show_region(&mut self, region: Rect, original_image_size: Vec2) {
+        const MARGIN: f32 = 2.0;
+        let relative_size = region.size() / original_image_size;
+        self.set_zoom(relative_size.max_elem() * MARGIN);
+        self.pan_offset = region.center().to_vec2() / original_image_size;
+    }
+
     pub fn pan_bounds(
         &self,
         original_image_size: Vec2,
@@ -60,37 +92,39 @@ impl ImageViewer {
     pub fn ui(
         &mut self,
         ui: &mut egui::Ui,
-        sources: impl Iterator<Item = ImageSource<'static>>,
+        sources: impl Iterator<Item = impl Into<ViewerLayer>>,
         sense: Option<Sense>,
     ) -> InnerResponse<Option<ImageViewerInteraction>> {
         let available_size = ui.available_size();
         let viewport_rect = ui.available_rect_before_wrap();
 
-        let mut iter = sources.map(|i| {
-            egui::Image::new(i)
+        let mut iter = sources.map(|layer| {
+            let ViewerLayer { source, tint } = layer.into();
+            let image = egui::Image::new(source)
                 .maintain_aspect_ratio(true)
                 // Important for Texture-ImageSources
                 .fit_to_exact_size(available_size)
                 .texture_options(TextureOptions {
                     magnification: egui::TextureFilter::Nearest,
                     ..Default::default()
-                })
+                });
+            (image, tint)
         });
         fn next_loaded(
-            iter: impl Iterator<Item = egui::Image<'static>>,
+            iter: impl Iterator<Item = (egui::Image<'static>, Color32)>,
             ui: &egui::Ui,
-        ) -> Option<(SizedTexture, egui::Image<'static>)> {
-            iter.filter_map(|image| {
+        ) -> Option<(SizedTexture, Color32)> {
+            iter.filter_map(|(image, tint)| {
                 let tlr = image.load_for_size(ui.ctx(), ui.available_size());
                 match tlr {
-                    Ok(TexturePoll::Ready { texture }) => Some((texture, image)),
+                    Ok(TexturePoll::Ready { texture }) => Some((texture, tint)),
                     _ => None,
                 }
             })
             .next()
         }
 
-        let Some((first_texture, _image)) = next_loaded(&mut iter, ui) else {
+        let Some((first_texture, first_tint)) = next_loaded(&mut iter, ui) else {
             return InnerResponse {
                 inner: None,
                 response: ui.response(),
@@ -177,14 +211,9 @@ impl ImageViewer {
         let image_rect_unclipped =
             Rect::from_min_size(viewport_rect.min + pixel_offset, image_size_px);
 
-        p.image(
-            first_texture.id,
-            image_rect_unclipped,
-            uv,
-            egui::Color32::WHITE,
-        );
-        while let Some((texture, _)) = next_loaded(&mut iter, ui) {
-            p.image(texture.id, image_rect_unclipped, uv, egui::Color32::WHITE);
+        p.image(first_texture.id, image_rect_unclipped, uv, first_tint);
+        while let Some((texture, tint)) = next_loaded(&mut iter, ui) {
+            p.image(texture.id, image_rect_unclipped, uv, tint);
         }
 
         let image_painter = ImagePainter::new(p, image_rect_unclipped, render_scale);
@@ -0,0 +1,18 @@
+#[test]
+fn deserialize_filter_chain() {
+    let chain: imanot::FilterChain = serde_json::from_str(
+        r#"[{"type": "gamma", "gamma": 0.5}, {"type": "invert"}, {"type": "median_denoise", "radius": 1}]"#,
+    )
+    .unwrap();
+    assert_eq!(
+        chain,
+        imanot::FilterChain(vec![
+            imanot::ImageFilter::Gamma { gamma: 0.5 },
+            imanot::ImageFilter::Invert,
+            imanot::ImageFilter::MedianDenoise { radius: 1 },
+        ])
+    );
+    let serialized = serde_json::to_string(&chain).unwrap();
+    let deserialized: imanot::FilterChain = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(chain, deserialized);
+}